@@ -0,0 +1,59 @@
+// cargo bench --bench broadphase
+// entity-entity collisions for a swarm of bullets, spatial hash vs every pair
+
+use rustlandkings::entity::*;
+use rustlandkings::vec2::*;
+use rustlandkings::systems::collision::*;
+
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+fn bullets(n: usize) -> BTreeMap<u32, Entity> {
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    let mut entities = BTreeMap::new();
+    // roughly a 40x40 level of 0.2 tiles, a few enemies among the bullets
+    for i in 0..n {
+        let pos = Vec2::new(rng.gen_range(0.0..8.0), rng.gen_range(0.0..8.0));
+        let entity = if i % 20 == 0 {
+            Entity::new_enemy(pos.x, pos.y)
+        } else {
+            let dir = Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)).normalize();
+            Entity::new_bullet(pos, dir, EntityForce::Player, 0)
+        };
+        entities.insert(rng.gen(), entity);
+    }
+    entities
+}
+
+fn time_it(iters: u32, mut f: impl FnMut() -> usize) -> (f64, usize) {
+    let start = Instant::now();
+    let mut n = 0;
+    for _ in 0..iters {
+        n = f();
+    }
+    (start.elapsed().as_secs_f64() * 1000.0 / iters as f64, n)
+}
+
+fn main() {
+    let dt = 1.0 / 60.0;
+    println!("{:>8} {:>12} {:>12} {:>10}", "entities", "hash ms", "brute ms", "collisions");
+    for &n in [100, 500, 1000, 2000, 5000].iter() {
+        let entities = bullets(n);
+        let iters = if n > 1000 { 3 } else { 20 };
+        let (hash_ms, hits) = time_it(iters, || {
+            let mut collisions = Vec::new();
+            simulate_entity_entity_collisions(&entities, &mut collisions, dt, 0.2);
+            collisions.len()
+        });
+        let (brute_ms, brute_hits) = time_it(iters, || {
+            let mut collisions = Vec::new();
+            simulate_entity_entity_collisions_brute_force(&entities, &mut collisions, dt);
+            collisions.len()
+        });
+        assert_eq!(hits, brute_hits);
+        println!("{:>8} {:>12.3} {:>12.3} {:>10}", n, hash_ms, brute_ms, hits);
+    }
+}
@@ -0,0 +1,244 @@
+use crate::grid::*;
+use crate::entity::*;
+use crate::simulation_state::*;
+
+use std::collections::VecDeque;
+
+/*
+numbers about a level so bad ones can be thrown out and generators compared.
+paths and regions are 4-connected tiles, same as Grid::flood_fill
+*/
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelReport {
+    pub tiles: usize,
+    pub ground_tiles: usize,
+    pub reachable_tiles: usize,         // from the player
+    pub open_ratio: f32,                // ground / all tiles
+    pub enemies: usize,
+    pub unreachable_enemies: usize,
+    pub enemy_distances: Vec<u32>,      // shortest path in tiles to each reachable enemy, nearest first
+    pub dead_ends: usize,               // reachable tiles with only one way out
+    pub choke_points: usize,            // reachable tiles that would cut the level in two if they were walls
+    pub spawns_moved: u32,              // nudged or relocated to fit, from the generators spawn report
+    pub spawns_dropped: usize,          // had no room at all, or only got room too close to the player
+}
+
+impl LevelReport {
+    // good enough to play
+    pub fn is_playable(&self) -> bool {
+        self.enemies > 0 && self.unreachable_enemies == 0
+    }
+
+    pub fn nearest_enemy(&self) -> Option<u32> {
+        self.enemy_distances.first().copied()
+    }
+
+    pub fn furthest_enemy(&self) -> Option<u32> {
+        self.enemy_distances.last().copied()
+    }
+
+    pub fn mean_enemy_distance(&self) -> f32 {
+        if self.enemy_distances.is_empty() {
+            return 0.0;
+        }
+        self.enemy_distances.iter().sum::<u32>() as f32 / self.enemy_distances.len() as f32
+    }
+
+    pub fn csv_header() -> &'static str {
+        "tiles,ground,reachable,open_ratio,enemies,unreachable_enemies,nearest_enemy,mean_enemy_distance,furthest_enemy,dead_ends,choke_points,spawns_moved,spawns_dropped,playable"
+    }
+
+    pub fn csv_row(&self) -> String {
+        let or_blank = |d: Option<u32>| d.map(|d| d.to_string()).unwrap_or_default();
+        format!("{},{},{},{:.3},{},{},{},{:.2},{},{},{},{},{},{}",
+            self.tiles, self.ground_tiles, self.reachable_tiles, self.open_ratio,
+            self.enemies, self.unreachable_enemies,
+            or_blank(self.nearest_enemy()), self.mean_enemy_distance(), or_blank(self.furthest_enemy()),
+            self.dead_ends, self.choke_points, self.spawns_moved, self.spawns_dropped, self.is_playable() as u8)
+    }
+}
+
+const DIRS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+fn is_ground(g: &Grid, x: i32, y: i32) -> bool {
+    g.get_2d(x, y) == Some(Tile::Ground)
+}
+
+// steps from (x, y) to every tile, None for walls and anything cut off
+pub fn path_lengths(g: &Grid, x: i32, y: i32) -> Vec<Option<u32>> {
+    let mut dist = vec![None; g.tiles.len()];
+    if !is_ground(g, x, y) {
+        return dist;
+    }
+    dist[(x + y * g.w) as usize] = Some(0);
+    let mut queue = VecDeque::new();
+    queue.push_back((x, y));
+    while let Some((x, y)) = queue.pop_front() {
+        let d = dist[(x + y * g.w) as usize].unwrap();
+        for (dx, dy) in DIRS.iter() {
+            let (nx, ny) = (x + dx, y + dy);
+            if !is_ground(g, nx, ny) { continue; }
+            let i = (nx + ny * g.w) as usize;
+            if dist[i].is_none() {
+                dist[i] = Some(d + 1);
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    dist
+}
+
+// (tile, parent, next direction to try)
+type DfsFrame = ((i32, i32), Option<(i32, i32)>, usize);
+
+// articulation points of the region containing start, iterative dfs (hopcroft tarjan)
+pub fn choke_points(g: &Grid, start: (i32, i32)) -> Vec<(i32, i32)> {
+    let mut result = Vec::new();
+    if !is_ground(g, start.0, start.1) {
+        return result;
+    }
+    let n = g.tiles.len();
+    let index = |(x, y): (i32, i32)| (x + y * g.w) as usize;
+    let mut disc = vec![0u32; n];   // 0 is unvisited
+    let mut low = vec![0u32; n];
+    let mut is_cut = vec![false; n];
+    let mut time = 1;
+
+    let mut stack: Vec<DfsFrame> = vec![(start, None, 0)];
+    disc[index(start)] = time;
+    low[index(start)] = time;
+    let mut root_children = 0;
+
+    while let Some(&mut (tile, parent, ref mut next)) = stack.last_mut() {
+        if *next < DIRS.len() {
+            let (dx, dy) = DIRS[*next];
+            *next += 1;
+            let child = (tile.0 + dx, tile.1 + dy);
+            if !is_ground(g, child.0, child.1) || Some(child) == parent { continue; }
+            if disc[index(child)] == 0 {
+                time += 1;
+                disc[index(child)] = time;
+                low[index(child)] = time;
+                if tile == start { root_children += 1; }
+                stack.push((child, Some(tile), 0));
+            } else {
+                low[index(tile)] = low[index(tile)].min(disc[index(child)]);
+            }
+        } else {
+            stack.pop();
+            if let Some(p) = parent {
+                low[index(p)] = low[index(p)].min(low[index(tile)]);
+                if p != start && low[index(tile)] >= disc[index(p)] {
+                    is_cut[index(p)] = true;
+                }
+            }
+        }
+    }
+    if root_children > 1 {
+        is_cut[index(start)] = true;
+    }
+
+    for y in 0..g.h {
+        for x in 0..g.w {
+            if is_cut[index((x, y))] {
+                result.push((x, y));
+            }
+        }
+    }
+    result
+}
+
+pub fn analyze_level(state: &SimulationState) -> LevelReport {
+    let g = &state.terrain;
+    let ground_tiles = g.tiles.iter().filter(|t| **t == Tile::Ground).count();
+
+    let player_tile = state.entities.values()
+        .find(|e| e.variety == EntityType::Player)
+        .map(|p| g.get_xy_of_position(p.aabb.center()));
+    let dist = match player_tile {
+        Some((x, y)) => path_lengths(g, x, y),
+        None => vec![None; g.tiles.len()],
+    };
+
+    let mut enemy_distances = Vec::new();
+    let mut enemies = 0;
+    for e in state.entities.values().filter(|e| e.force == EntityForce::Enemy && e.variety != EntityType::Bullet) {
+        enemies += 1;
+        let (x, y) = g.get_xy_of_position(e.aabb.center());
+        if let Some(Some(d)) = g.get_2d(x, y).and(dist.get((x + y * g.w) as usize)) {
+            enemy_distances.push(*d);
+        }
+    }
+    enemy_distances.sort();
+
+    let mut reachable_tiles = 0;
+    let mut dead_ends = 0;
+    for y in 0..g.h {
+        for x in 0..g.w {
+            if dist[(x + y * g.w) as usize].is_none() { continue; }
+            reachable_tiles += 1;
+            let exits = DIRS.iter().filter(|(dx, dy)| is_ground(g, x + dx, y + dy)).count();
+            if exits == 1 {
+                dead_ends += 1;
+            }
+        }
+    }
+
+    LevelReport {
+        tiles: g.tiles.len(),
+        ground_tiles: ground_tiles,
+        reachable_tiles: reachable_tiles,
+        open_ratio: ground_tiles as f32 / g.tiles.len() as f32,
+        enemies: enemies,
+        unreachable_enemies: enemies - enemy_distances.len(),
+        enemy_distances: enemy_distances,
+        dead_ends: dead_ends,
+        choke_points: player_tile.map(|t| choke_points(g, t).len()).unwrap_or(0),
+        spawns_moved: state.spawn_report.nudged + state.spawn_report.relocated,
+        spawns_dropped: state.spawn_report.dropped.len() + state.spawn_report.too_close.len(),
+    }
+}
+
+#[test]
+fn test_choke_points() {
+    use crate::level::*;
+
+    // two rooms joined by a corridor, the corridor tiles and the doorways are chokes
+    let state = parse_level("\
+##########
+#...#....#
+#p.......#
+#...#....#
+##########
+", 1).unwrap();
+    let chokes = choke_points(&state.terrain, (1, 2));
+    assert_eq!(chokes, vec![(3, 2), (4, 2), (5, 2)]);
+
+    // open room has none
+    let state = parse_level("#####\n#p..#\n#...#\n#...#\n#####\n", 1).unwrap();
+    assert!(choke_points(&state.terrain, (1, 1)).is_empty());
+}
+
+#[test]
+fn test_analyze_level() {
+    use crate::level::*;
+
+    let state = parse_level("\
+#########
+#p..#...#
+###.#.e.#
+#e#.....#
+#########
+", 1).unwrap();
+    let report = analyze_level(&state);
+    assert_eq!(report.tiles, 45);
+    assert_eq!(report.ground_tiles, 16);
+    assert_eq!(report.reachable_tiles, 15);
+    assert_eq!(report.enemies, 2);
+    assert_eq!(report.unreachable_enemies, 1);
+    assert_eq!(report.enemy_distances, vec![8]);
+    assert!(!report.is_playable());
+    assert_eq!(report.dead_ends, 1);   // the player's corner
+    assert_eq!(report.csv_row().split(',').count(), LevelReport::csv_header().split(',').count());
+}
@@ -0,0 +1,27 @@
+// rgba colour so the simulation doesnt need sdl, turns into an sdl colour when drawing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+// named like sdls so the call sites dont change
+#[allow(non_snake_case)]
+impl Color {
+    pub const fn RGB(r: u8, g: u8, b: u8) -> Color {
+        Color{r: r, g: g, b: b, a: 255}
+    }
+
+    pub const fn RGBA(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color{r: r, g: g, b: b, a: a}
+    }
+}
+
+#[cfg(feature = "sdl")]
+impl From<Color> for sdl2::pixels::Color {
+    fn from(c: Color) -> sdl2::pixels::Color {
+        sdl2::pixels::Color::RGBA(c.r, c.g, c.b, c.a)
+    }
+}
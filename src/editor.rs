@@ -0,0 +1,132 @@
+use crate::grid::*;
+use crate::entity::*;
+use crate::level::*;
+use crate::vec2::*;
+use crate::simulation_state::*;
+
+// brushes are level file characters: # wall, . ground, or an entity letter
+
+pub const EDITOR_BRUSHES: [char; 16] = ['.', '#', 'p', 'c', 'r', 'e', 's', 'y', 'b', 'k', 'd', '1', '2', '3', '4', '5'];
+
+fn tile_at(state: &SimulationState, pos: Vec2) -> Option<(i32, i32)> {
+    let (x, y) = state.terrain.get_xy_of_position(pos);
+    if pos.x < 0.0 || pos.y < 0.0 || state.terrain.get_2d(x, y).is_none() {
+        None
+    } else {
+        Some((x, y))
+    }
+}
+
+pub fn paint_tile(state: &mut SimulationState, pos: Vec2, tile: Tile) {
+    if let Some((x, y)) = tile_at(state, pos) {
+        state.terrain.set_2d(x, y, tile);
+    }
+}
+
+// everything whose center is in the tile under pos
+pub fn delete_entities_at(state: &mut SimulationState, pos: Vec2) {
+    if let Some(tile) = tile_at(state, pos) {
+        let doomed: Vec<u32> = state.entities.iter()
+            .filter(|(_, e)| state.terrain.get_xy_of_position(e.aabb.center()) == tile)
+            .map(|(id, _)| *id)
+            .collect();
+        state.retain_entities(|id, _| !doomed.contains(id));
+    }
+}
+
+// one entity per tile like the level format, and only ever one player
+pub fn place_entity(state: &mut SimulationState, pos: Vec2, c: char) -> Option<u32> {
+    let (x, y) = tile_at(state, pos)?;
+    let center = state.terrain.get_rect_2d(x, y).center();
+    let entity = match c {
+        // level files are always LEVEL_TILE_SIZE, but this might be a generated level with tuned tiles
+        'd' => Entity::new_door(center.x, center.y, state.terrain.elem_w),
+        c => entity_for_char(c, center.x, center.y)?,
+    };
+
+    delete_entities_at(state, center);
+    if entity.variety == EntityType::Player {
+        state.retain_entities(|_, e| e.variety != EntityType::Player);
+    }
+    state.terrain.set_2d(x, y, Tile::Ground);
+
+    let id = new_entity_id(&state.entities, &mut state.rng);
+    state.entities.insert(id, entity);
+    Some(id)
+}
+
+pub fn apply_brush(state: &mut SimulationState, pos: Vec2, brush: char) {
+    match brush {
+        '#' => {
+            delete_entities_at(state, pos);
+            paint_tile(state, pos, Tile::Wall);
+        },
+        '.' => paint_tile(state, pos, Tile::Ground),
+        c => { place_entity(state, pos, c); },
+    }
+}
+
+#[test]
+fn test_editor() {
+    let mut state = parse_level("#####\n#p..#\n#...#\n#####\n", 1).unwrap();
+    let tile = |x: f32, y: f32| Vec2::new((x + 0.5) * LEVEL_TILE_SIZE, (y + 0.5) * LEVEL_TILE_SIZE);
+
+    apply_brush(&mut state, tile(0.0, 2.0), '.');
+    apply_brush(&mut state, tile(2.0, 1.0), '#');
+    apply_brush(&mut state, tile(3.0, 2.0), 's');
+    apply_brush(&mut state, tile(3.0, 2.0), 'c');   // replaces the swarmer
+    apply_brush(&mut state, tile(1.0, 2.0), 'p');   // moves the player
+    apply_brush(&mut state, tile(9.0, 9.0), '#');   // off the map, nothing
+    assert_eq!(level_to_string(&state), "#####\n#.#.#\n.p.c#\n#####\n");
+
+    delete_entities_at(&mut state, tile(3.0, 2.0));
+    assert_eq!(level_to_string(&state), "#####\n#.#.#\n.p..#\n#####\n");
+}
+
+#[test]
+fn test_editor_key_and_door() {
+    let mut state = parse_level("#####\n#p..#\n#####\n", 1).unwrap();
+    let mut terrain = Grid::new(5, 3, 0.3, 0.3);
+    terrain.tiles = state.terrain.tiles.clone();
+    state.terrain = terrain;
+    let tile = |x: f32, y: f32| Vec2::new((x + 0.5) * 0.3, (y + 0.5) * 0.3);
+
+    apply_brush(&mut state, tile(2.0, 1.0), 'k');
+    apply_brush(&mut state, tile(3.0, 1.0), 'd');
+    assert_eq!(level_to_string(&state), "#####\n#pkd#\n#####\n");
+    // fills the tile its on, not a level file sized one
+    let door = state.entities.values().find(|e| e.variety == EntityType::Door).unwrap();
+    assert!((door.aabb.w - 0.3 * 0.98).abs() < 0.0001);
+}
+
+#[test]
+fn test_editor_gun_pickups() {
+    use crate::systems::projectiles::*;
+
+    let mut state = parse_level("#######\n#p....#\n#######\n", 1).unwrap();
+    let tile = |x: f32, y: f32| Vec2::new((x + 0.5) * LEVEL_TILE_SIZE, (y + 0.5) * LEVEL_TILE_SIZE);
+
+    for (i, c) in ['1', '3', '5'].iter().enumerate() {
+        apply_brush(&mut state, tile(2.0 + i as f32, 1.0), *c);
+    }
+    assert_eq!(level_to_string(&state), "#######\n#p135.#\n#######\n");
+    assert!(state.entities.values().any(|e| e.variety == EntityType::GunPickup(GunKind::Scattergun)));
+}
+
+#[test]
+fn test_editor_drops_inventories() {
+    use crate::systems::projectiles::*;
+
+    let mut state = parse_level("######\n#pe..#\n######\n", 1).unwrap();
+    let tile = |x: f32, y: f32| Vec2::new((x + 0.5) * LEVEL_TILE_SIZE, (y + 0.5) * LEVEL_TILE_SIZE);
+    let ids: Vec<u32> = state.entities.keys().copied().collect();
+    for id in ids.iter() {
+        state.pick_up_gun(*id, Gun::new_shotgun());
+        state.pick_up_gun(*id, Gun::new_makina());
+    }
+    assert_eq!(state.inventories.len(), 2);
+
+    apply_brush(&mut state, tile(2.0, 1.0), '#');   // paints over the enemy
+    apply_brush(&mut state, tile(4.0, 1.0), 'p');   // replaces the player
+    assert!(state.inventories.is_empty());
+}
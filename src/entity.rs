@@ -1,11 +1,9 @@
 use crate::rect::*;
 use crate::vec2::*;
-use crate::game::*;
 use crate::side_effect::*;
 use crate::systems::projectiles::*;
 
-use sdl2::controller::GameController;
-use sdl2::pixels::Color;
+use crate::color::*;
 
 
 pub enum PlatformHeight {
@@ -32,9 +30,10 @@ pub enum CollisionGroup {
     Bullet,
     Static,
     Other,    
+    Item,   // lying around to be picked up, nothing bumps into them
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum EntityType {
     Player,
     Bullet,
@@ -44,8 +43,14 @@ pub enum EntityType {
     Swarmer,
     Sprayer,
     Bigdog,
+    ThrownGun,
+    Key,
+    Door,
+    GunPickup(GunKind),
 }
 
+pub const PLAYER_MAX_HEALTH: f32 = 5.0;
+
 #[derive(Debug, Clone, Copy)]
 pub struct Entity {
     pub force: EntityForce,
@@ -59,6 +64,8 @@ pub struct Entity {
     pub draw_order: DrawOrder,
     pub health: f32,
     pub last_hit: f32, // for iframes etc
+    pub boost_until: f32,   // faster until then, from eating a gun
+    pub shield_until: f32,  // no damage until then, same
 
     pub speed: f32,
 
@@ -68,6 +75,14 @@ pub struct Entity {
 }
 
 impl Entity {
+    pub fn is_boosted(&self, time: f32) -> bool {
+        time < self.boost_until
+    }
+
+    pub fn is_shielded(&self, time: f32) -> bool {
+        time < self.shield_until
+    }
+
     pub fn new_player(x: f32, y: f32) -> Entity {
         Entity {
             aabb: Rect::new_centered(x, y, 0.05, 0.05),
@@ -76,8 +91,10 @@ impl Entity {
             draw_order: DrawOrder::Front,
             force: EntityForce::Player,
             collision_group: CollisionGroup::Other,
-            health: 5.0,
+            health: PLAYER_MAX_HEALTH,
             last_hit: 0.0,
+            boost_until: 0.0,
+            shield_until: 0.0,
             variety: EntityType::Player,
             source: 0,
             melee_damage: 0.0,
@@ -97,6 +114,8 @@ impl Entity {
             collision_group: CollisionGroup::Other,
             health: 5.0,
             last_hit: 0.0,
+            boost_until: 0.0,
+            shield_until: 0.0,
             variety: EntityType::Enemy,
             source: 0,
             melee_damage: 0.0,
@@ -115,6 +134,8 @@ impl Entity {
             collision_group: CollisionGroup::Other,
             health: 5.0,
             last_hit: 0.0,
+            boost_until: 0.0,
+            shield_until: 0.0,
             variety: EntityType::Sprayer,
             source: 0,
             melee_damage: 0.0,
@@ -133,6 +154,8 @@ impl Entity {
             collision_group: CollisionGroup::Other,
             health: 10.0,
             last_hit: 0.0,
+            boost_until: 0.0,
+            shield_until: 0.0,
             variety: EntityType::Bigdog,
             source: 0,
             melee_damage: 0.0,
@@ -163,6 +186,8 @@ impl Entity {
             velocity: Vec2::zero(),
             draw_order: DrawOrder::Back, health: 4.0, 
             last_hit: 0.0,
+            boost_until: 0.0,
+            shield_until: 0.0,
             source: 0,
             melee_damage: 0.0,
             look_direction: Vec2::new(1.0, 0.0),
@@ -181,6 +206,8 @@ impl Entity {
             velocity: Vec2::zero(),
             draw_order: DrawOrder::Back, health: 10.0, 
             last_hit: 0.0,
+            boost_until: 0.0,
+            shield_until: 0.0,
             source: 0,
             melee_damage: 0.0,
             look_direction: Vec2::new(1.0, 0.0),
@@ -189,6 +216,50 @@ impl Entity {
         }
     }
 
+    // plugs a corridor until the key is picked up. nearly a whole tile so nothing squeezes past,
+    // and it cant be shot open
+    pub fn new_door(x: f32, y: f32, tile_size: f32) -> Entity {
+        let mut door = Entity::new_crate(x, y);
+        door.variety = EntityType::Door;
+        door.aabb = Rect::new_centered(x, y, tile_size * 0.98, tile_size * 0.98);
+        door.colour = Color::RGB(120, 72, 24);
+        door.health = f32::INFINITY;
+        door
+    }
+
+    pub fn new_key(x: f32, y: f32) -> Entity {
+        let mut key = Entity::new_crate(x, y);
+        key.collision_group = CollisionGroup::Item;
+        key.variety = EntityType::Key;
+        key.aabb = Rect::new_centered(x, y, 0.06, 0.06);
+        key.colour = Color::RGB(255, 215, 0);
+        key.health = f32::INFINITY;
+        key
+    }
+
+    // walk over it and it goes on the back of your queue
+    pub fn new_gun_pickup(x: f32, y: f32, kind: GunKind) -> Entity {
+        let mut pickup = Entity::new_crate(x, y);
+        pickup.collision_group = CollisionGroup::Item;
+        pickup.variety = EntityType::GunPickup(kind);
+        pickup.aabb = Rect::new_centered(x, y, 0.05, 0.05);
+        pickup.colour = Color::RGB(160, 160, 160);
+        pickup.health = f32::INFINITY;
+        pickup
+    }
+
+    // flies like a bullet and goes off when it hits something, carrying whatever ammo was left
+    pub fn new_thrown_gun(from: Vec2, dir: Vec2, force: EntityForce, source: u32, gun: Gun) -> Entity {
+        let mut thrown = Entity::new_bullet(from, dir, force, source);
+        thrown.variety = EntityType::ThrownGun;
+        thrown.aabb = Rect::new_centered(from.x, from.y, 0.04, 0.04);
+        thrown.colour = Color::RGB(160, 160, 160);
+        thrown.velocity = dir.mul_scalar(THROW_SPEED);
+        thrown.melee_damage = 0.0;
+        thrown.gun = gun;
+        thrown
+    }
+
     pub fn new_bullet(from: Vec2, dir: Vec2, force: EntityForce, source: u32) -> Entity {
         let bullet_s = 0.02;
         let bullet_speed = 0.7;
@@ -202,6 +273,8 @@ impl Entity {
             collision_group: CollisionGroup::Bullet,
             health: 1.0,
             last_hit: 0.0,
+            boost_until: 0.0,
+            shield_until: 0.0,
             variety: EntityType::Bullet,
             source: source,
             melee_damage: 1.0,
@@ -0,0 +1,74 @@
+use crate::rect::*;
+use crate::screen_transform::*;
+
+use sdl2::pixels::Color;
+use sdl2::video::Window;
+use sdl2::render::Canvas;
+
+// tiny 3x5 font made of rects since we dont have sdl_ttf. numbers, letters (no case) and a bit of punctuation
+
+fn glyph(c: char) -> Option<[u8; 5]> {
+    // each row is 3 bits, msb on the left
+    match c.to_ascii_lowercase() {
+        '0' => Some([0b111, 0b101, 0b101, 0b101, 0b111]),
+        '1' => Some([0b010, 0b110, 0b010, 0b010, 0b111]),
+        '2' => Some([0b111, 0b001, 0b111, 0b100, 0b111]),
+        '3' => Some([0b111, 0b001, 0b111, 0b001, 0b111]),
+        '4' => Some([0b101, 0b101, 0b111, 0b001, 0b001]),
+        '5' => Some([0b111, 0b100, 0b111, 0b001, 0b111]),
+        '6' => Some([0b111, 0b100, 0b111, 0b101, 0b111]),
+        '7' => Some([0b111, 0b001, 0b001, 0b001, 0b001]),
+        '8' => Some([0b111, 0b101, 0b111, 0b101, 0b111]),
+        '9' => Some([0b111, 0b101, 0b111, 0b001, 0b111]),
+        '.' => Some([0b000, 0b000, 0b000, 0b000, 0b010]),
+        '-' => Some([0b000, 0b000, 0b111, 0b000, 0b000]),
+        ':' => Some([0b000, 0b010, 0b000, 0b010, 0b000]),
+        'a' => Some([0b010, 0b101, 0b111, 0b101, 0b101]),
+        'b' => Some([0b110, 0b101, 0b110, 0b101, 0b110]),
+        'c' => Some([0b011, 0b100, 0b100, 0b100, 0b011]),
+        'd' => Some([0b110, 0b101, 0b101, 0b101, 0b110]),
+        'e' => Some([0b111, 0b100, 0b110, 0b100, 0b111]),
+        'f' => Some([0b111, 0b100, 0b110, 0b100, 0b100]),
+        'g' => Some([0b011, 0b100, 0b101, 0b101, 0b011]),
+        'h' => Some([0b101, 0b101, 0b111, 0b101, 0b101]),
+        'i' => Some([0b111, 0b010, 0b010, 0b010, 0b111]),
+        'j' => Some([0b001, 0b001, 0b001, 0b101, 0b010]),
+        'k' => Some([0b101, 0b101, 0b110, 0b101, 0b101]),
+        'l' => Some([0b100, 0b100, 0b100, 0b100, 0b111]),
+        'm' => Some([0b101, 0b111, 0b111, 0b101, 0b101]),
+        'n' => Some([0b110, 0b101, 0b101, 0b101, 0b101]),
+        'o' => Some([0b010, 0b101, 0b101, 0b101, 0b010]),
+        'p' => Some([0b110, 0b101, 0b110, 0b100, 0b100]),
+        'q' => Some([0b010, 0b101, 0b101, 0b110, 0b011]),
+        'r' => Some([0b110, 0b101, 0b110, 0b101, 0b101]),
+        's' => Some([0b011, 0b100, 0b010, 0b001, 0b110]),
+        't' => Some([0b111, 0b010, 0b010, 0b010, 0b010]),
+        'u' => Some([0b101, 0b101, 0b101, 0b101, 0b111]),
+        'v' => Some([0b101, 0b101, 0b101, 0b101, 0b010]),
+        'w' => Some([0b101, 0b101, 0b111, 0b111, 0b101]),
+        'x' => Some([0b101, 0b101, 0b010, 0b101, 0b101]),
+        'y' => Some([0b101, 0b101, 0b010, 0b010, 0b010]),
+        'z' => Some([0b111, 0b001, 0b010, 0b100, 0b111]),
+        _ => None,
+    }
+}
+
+// x, y are screen space, pixel is the size of one font pixel. returns width drawn
+pub fn draw_text(canvas: &mut Canvas<Window>, transform: &ScreenTransform, text: &str, x: f32, y: f32, pixel: f32, colour: Color) -> f32 {
+    canvas.set_draw_color(colour);
+    let mut cursor = x;
+    for c in text.chars() {
+        if let Some(rows) = glyph(c) {
+            for (j, row) in rows.iter().enumerate() {
+                for i in 0..3 {
+                    if row & (0b100 >> i) != 0 {
+                        let r = Rect::new(cursor + i as f32 * pixel, y + j as f32 * pixel, pixel, pixel);
+                        canvas.fill_rect(transform.sdl_rect(r)).unwrap();
+                    }
+                }
+            }
+        }
+        cursor += 4.0 * pixel;
+    }
+    cursor - x
+}
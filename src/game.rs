@@ -1,8 +1,11 @@
+#[cfg(feature = "sdl")]
 use crate::rect::*;
 use crate::entity::*;
 use crate::screen_transform::*;
+#[cfg(feature = "sdl")]
 use crate::grid::*;
 use crate::systems::melee_damage::handle_melee_damage;
+#[cfg(feature = "sdl")]
 use crate::vec2::*;
 use crate::side_effect::*;
 use crate::simulation_state::*;
@@ -10,17 +13,22 @@ use crate::systems::command::*;
 use crate::systems::collision::*;
 use crate::systems::projectiles::*;
 use crate::systems::ai::*;
-
-use sdl2::pixels::Color;
-use sdl2::video::Window;
-use sdl2::render::Canvas;
-use sdl2::event::Event;
-use sdl2::EventPump;
-use sdl2::keyboard::Keycode;
-use sdl2::keyboard::KeyboardState;
-use sdl2::keyboard::Scancode;
-use sdl2::mouse::MouseState;
-use std::collections::HashMap;
+use crate::systems::pickups::*;
+use crate::replay::*;
+use crate::leaderboard::*;
+use crate::rating::*;
+use crate::level::*;
+#[cfg(feature = "sdl")]
+use crate::editor::*;
+use crate::generators::*;
+use crate::generators::params::*;
+#[cfg(feature = "sdl")]
+use crate::font::*;
+use crate::pathfinding::*;
+
+#[cfg(feature = "sdl")]
+use sdl2::{pixels::Color, video::Window, render::Canvas, event::Event, EventPump, keyboard::Keycode, keyboard::Scancode, mouse::MouseButton};
+#[cfg(feature = "sdl")]
 use rand::Rng;
 pub struct Game {
     pause: bool,
@@ -31,13 +39,50 @@ pub struct Game {
     frame_side_effects: Vec<SideEffect>,
 
     player_id: u32,
+    #[cfg(feature = "sdl")]
     transform: ScreenTransform,
 
     state: SimulationState,
+    flow_field: Option<FlowField>,  // to the player, redone when they change tile
+    pathfinder: Option<Pathfinder>, // redone when the terrain changes, ie. a new level or leaving the editor
+    level: Option<String>,  // authored level text, otherwise its generated from the seed
+    generator: Generator,
+    gen_params: LevelGenParams,
+    gen_params_path: Option<String>,
+    tuning: bool,   // level generator knobs on screen
+    #[cfg(feature = "sdl")]
+    tuning_cursor: usize,
+
+    recording: Replay,
+    unrecorded_edits: bool,    // editor changes the recording doesnt know about yet
+    playback: Option<(Replay, usize)>,  // replay, next frame
+
+    kills: u32,
+    damage_taken: f32,
+    run_over: bool,
+    ranked: bool,   // off for replays and loaded snapshots
+
+    leaderboard: Leaderboard,
+    leaderboard_path: Option<String>,
+    show_leaderboard: bool,
+    #[cfg(feature = "sdl")]
+    leaderboard_cursor: usize,
+
+    profile: String,
+    ratings: Ratings,
+    ratings_path: Option<String>,
+
+    editing: bool,
+    #[cfg(feature = "sdl")]
+    editor_brush: char,
+    #[cfg(feature = "sdl")]
+    editor_cursor: Vec2,
 }
 
 impl Game {
-    pub fn new(transform: ScreenTransform) -> Game {
+    // the transform is only for drawing, a headless build has nothing to keep it for
+    #[cfg_attr(not(feature = "sdl"), allow(unused_variables))]
+    pub fn new(transform: ScreenTransform, seed: u64) -> Game {
         let mut game = Game { 
             pause: false, 
             frame_commands: Vec::new(),
@@ -45,9 +90,39 @@ impl Game {
             frame_movements: Vec::new(), 
             frame_side_effects: Vec::new(), 
             player_id: 0,
+            #[cfg(feature = "sdl")]
             transform: transform, 
             //state: SimulationState::new()
-            state: generate_level_drunk(),
+            state: generate_level_drunk(seed, &LevelGenParams::default()),
+            flow_field: None,
+            pathfinder: None,
+            level: None,
+            generator: Generator::Drunk,
+            gen_params: LevelGenParams::default(),
+            gen_params_path: None,
+            tuning: false,
+            #[cfg(feature = "sdl")]
+            tuning_cursor: 0,
+            recording: Replay::new(seed, Generator::Drunk),
+            unrecorded_edits: false,
+            playback: None,
+            kills: 0,
+            damage_taken: 0.0,
+            run_over: false,
+            ranked: true,
+            leaderboard: Leaderboard::new(),
+            leaderboard_path: None,
+            show_leaderboard: false,
+            #[cfg(feature = "sdl")]
+            leaderboard_cursor: 0,
+            profile: "player".to_string(),
+            ratings: Ratings::new(),
+            ratings_path: None,
+            editing: false,
+            #[cfg(feature = "sdl")]
+            editor_brush: '#',
+            #[cfg(feature = "sdl")]
+            editor_cursor: Vec2::zero(),
         };
 
         game.initialize();
@@ -56,6 +131,8 @@ impl Game {
     }
 
     pub fn initialize(&mut self) {
+        self.flow_field = None;
+        self.pathfinder = None;
         for (entity_id, entity) in self.state.entities.iter() {
             if entity.variety == EntityType::Player {
                 self.player_id = *entity_id;
@@ -63,6 +140,164 @@ impl Game {
         }
     }
 
+    pub fn state(&self) -> &SimulationState {
+        &self.state
+    }
+
+    pub fn player_id(&self) -> u32 {
+        self.player_id
+    }
+
+    pub fn recording(&self) -> &Replay {
+        &self.recording
+    }
+
+    pub fn is_playing_back(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    // play an authored level instead of generated ones, resets and replays will use it too
+    pub fn set_level(&mut self, text: &str, seed: u64) -> std::io::Result<()> {
+        parse_level(text, seed)?;
+        self.level = Some(text.to_string());
+        self.restart(seed);
+        Ok(())
+    }
+
+    pub fn set_generator(&mut self, generator: Generator, seed: u64) {
+        self.generator = generator;
+        self.restart(seed);
+    }
+
+    pub fn restart(&mut self, seed: u64) {
+        println!("===== starting seed {} =====", seed);
+        self.state = match &self.level {
+            Some(text) => parse_level(text, seed).expect("level was checked in set_level"),
+            None => self.generator.generate(seed, &self.gen_params),
+        };
+        self.recording = self.new_recording(seed);
+        self.playback = None;
+        self.kills = 0;
+        self.damage_taken = 0.0;
+        self.run_over = false;
+        self.unrecorded_edits = false;
+        // seederboard is for untweaked drunk levels
+        self.ranked = self.level.is_none() && self.generator == Generator::Drunk && self.gen_params == LevelGenParams::default();
+        self.initialize();
+    }
+
+    // everything playback needs to rebuild the level, frames get added as it goes
+    fn new_recording(&self, seed: u64) -> Replay {
+        let mut replay = Replay::new(seed, self.generator);
+        replay.gen_params = self.gen_params.clone();
+        replay.level = self.level.clone();
+        replay
+    }
+
+    // restart the replays level and feed its commands instead of input and ai
+    pub fn start_playback(&mut self, replay: Replay) {
+        self.generator = replay.generator;
+        self.gen_params = replay.gen_params.clone();
+        self.level = replay.level.clone();
+        self.restart(replay.seed);
+        if let Some(text) = &replay.snapshot {
+            self.load_state(SimulationState::deserialize(text).expect("snapshot was checked when the replay loaded"));
+        }
+        self.playback = Some((replay, 0));
+        self.ranked = false;
+    }
+
+    // carry on from a snapshot. the recording starts over from it, and it wont be ranked
+    pub fn load_state(&mut self, state: SimulationState) {
+        self.recording = self.new_recording(state.seed);
+        self.recording.snapshot = Some(state.serialize());
+        self.unrecorded_edits = false;
+        self.playback = None;
+        self.ranked = false;
+        self.state = state;
+        self.initialize();
+    }
+
+    pub fn gen_params(&self) -> &LevelGenParams {
+        &self.gen_params
+    }
+
+    // tuning gets saved back to path, regenerates if anything changed
+    pub fn load_gen_params(&mut self, path: &str) -> std::io::Result<()> {
+        let params = LevelGenParams::load(path)?;
+        self.gen_params_path = Some(path.to_string());
+        self.set_gen_params(params);
+        Ok(())
+    }
+
+    pub fn set_gen_params(&mut self, params: LevelGenParams) {
+        if params != self.gen_params {
+            self.gen_params = params;
+            self.restart(self.state.seed);
+        }
+    }
+
+    pub fn leaderboard(&self) -> &Leaderboard {
+        &self.leaderboard
+    }
+
+    // finished runs get written back to path
+    pub fn load_leaderboard(&mut self, path: &str) -> std::io::Result<()> {
+        self.leaderboard = Leaderboard::load(path)?;
+        self.leaderboard_path = Some(path.to_string());
+        Ok(())
+    }
+
+    pub fn ratings(&self) -> &Ratings {
+        &self.ratings
+    }
+
+    // finished runs update the ratings and get written back to path
+    pub fn load_ratings(&mut self, path: &str) -> std::io::Result<()> {
+        self.ratings = Ratings::load(path)?;
+        self.ratings_path = Some(path.to_string());
+        Ok(())
+    }
+
+    // names go in a whitespace separated file
+    pub fn set_profile(&mut self, name: &str) {
+        self.profile = name.split_whitespace().collect::<Vec<_>>().join("_");
+    }
+
+    fn finish_run(&mut self, completed: bool) {
+        self.run_over = true;
+        if !self.ranked { return; }
+
+        let result = RunResult {
+            seed: self.state.seed,
+            completed: completed,
+            time: self.state.time,
+            kills: self.kills,
+            damage_taken: self.damage_taken,
+        };
+        println!("run over: {:?}", result);
+        self.leaderboard.add_run(result);
+        if let Some(path) = &self.leaderboard_path {
+            if let Err(e) = self.leaderboard.save(path) {
+                println!("couldnt save leaderboard: {}", e);
+            }
+        }
+
+        self.ratings.record_match(&self.profile, result.seed, completed);
+        println!("{} now rated {:.0}, seed {} rated {:.0}",
+            self.profile, self.ratings.player(&self.profile).rating, result.seed, self.ratings.seed(result.seed).rating);
+        if let Some(path) = &self.ratings_path {
+            if let Err(e) = self.ratings.save(path) {
+                println!("couldnt save ratings: {}", e);
+            }
+        }
+    }
+
+    // feed commands without going through sdl, eg. headless runs
+    pub fn push_commands(&mut self, commands: &[Command]) {
+        self.frame_commands.extend_from_slice(commands);
+    }
+
     pub fn clear_arenas(&mut self) {
         self.frame_commands.clear();
         self.frame_collisions.clear();
@@ -70,6 +305,11 @@ impl Game {
         self.frame_side_effects.clear();
     }
 
+}
+
+// input and drawing, the only parts that need sdl
+#[cfg(feature = "sdl")]
+impl Game {
     pub fn handle_input(&mut self, event_pump: &mut EventPump) -> bool {
         
         // Handle keys held
@@ -95,9 +335,23 @@ impl Game {
         let mouse = event_pump.mouse_state();
 
         let aim_pos = self.transform.pick_world(mouse.x() as u32, mouse.y() as u32);
-        if let Some(mut player_ent) = self.state.entities.get_mut(&self.player_id) {
+        self.editor_cursor = aim_pos;
+        if self.editing {
+            // tiles paint while held, entities go down one per click
+            if mouse.left() && (self.editor_brush == '#' || self.editor_brush == '.') {
+                apply_brush(&mut self.state, aim_pos, self.editor_brush);
+                self.ranked = false;
+                self.unrecorded_edits = true;
+            }
+            if mouse.right() {
+                delete_entities_at(&mut self.state, aim_pos);
+                self.ranked = false;
+                self.unrecorded_edits = true;
+            }
+        }
+        if let Some(player_ent) = self.state.entities.get(&self.player_id) {
             self.transform.translate_center(player_ent.aabb.center().lerp(aim_pos, 0.3));
-            player_ent.look_direction = aim_pos.sub(player_ent.aabb.center()).normalize();
+            self.frame_commands.push(Command::Look(self.player_id, aim_pos.sub(player_ent.aabb.center()).normalize()));
             
             // Handle held mouse
             if mouse.left() && player_ent.gun.automatic {
@@ -108,6 +362,109 @@ impl Game {
 
         // Handle events
         for event in event_pump.poll_iter() {
+            if self.editing {
+                let brush_key = match event {
+                    Event::KeyDown{keycode: Some(Keycode::Num1), ..} => Some(0),
+                    Event::KeyDown{keycode: Some(Keycode::Num2), ..} => Some(1),
+                    Event::KeyDown{keycode: Some(Keycode::Num3), ..} => Some(2),
+                    Event::KeyDown{keycode: Some(Keycode::Num4), ..} => Some(3),
+                    Event::KeyDown{keycode: Some(Keycode::Num5), ..} => Some(4),
+                    Event::KeyDown{keycode: Some(Keycode::Num6), ..} => Some(5),
+                    Event::KeyDown{keycode: Some(Keycode::Num7), ..} => Some(6),
+                    Event::KeyDown{keycode: Some(Keycode::Num8), ..} => Some(7),
+                    Event::KeyDown{keycode: Some(Keycode::Num9), ..} => Some(8),
+                    _ => None,
+                };
+                if let Some(i) = brush_key {
+                    self.editor_brush = EDITOR_BRUSHES[i];
+                    continue;
+                }
+                match event {
+                    Event::MouseButtonDown{mouse_btn: MouseButton::Left, ..} => {
+                        apply_brush(&mut self.state, self.editor_cursor, self.editor_brush);
+                        self.ranked = false;
+                        self.unrecorded_edits = true;
+                        continue;
+                    },
+                    // the wheel goes through every brush, the number keys only reach the first nine
+                    Event::MouseWheel{y, ..} => {
+                        let i = EDITOR_BRUSHES.iter().position(|c| *c == self.editor_brush).unwrap_or(0) as i32;
+                        self.editor_brush = EDITOR_BRUSHES[(i - y).rem_euclid(EDITOR_BRUSHES.len() as i32) as usize];
+                        continue;
+                    },
+                    // right is deleting, done from the held buttons above. no clicks get through to shooting
+                    Event::MouseButtonDown{..} | Event::MouseButtonUp{..} => continue,
+                    Event::KeyDown{keycode: Some(Keycode::F3), ..} => {
+                        // keep playing it after a reset too
+                        let text = level_to_string(&self.state);
+                        match parse_level(&text, self.state.seed) {
+                            Ok(_) => {
+                                match save_level(&self.state, "edited_level.txt") {
+                                    Ok(()) => println!("saved edited_level.txt"),
+                                    Err(e) => println!("couldnt save level: {}", e),
+                                }
+                                self.level = Some(text);
+                            },
+                            Err(e) => println!("not saving, level is broken: {}", e),
+                        }
+                        continue;
+                    },
+                    _ => {},
+                }
+            }
+            if self.tuning {
+                // every change regenerates the same seed so you can see what it did
+                let steps = match event {
+                    Event::KeyDown{keycode: Some(Keycode::Left), ..} => -1,
+                    Event::KeyDown{keycode: Some(Keycode::Right), ..} => 1,
+                    _ => 0,
+                };
+                if steps != 0 {
+                    self.gen_params.adjust_knob(self.tuning_cursor, steps);
+                    self.restart(self.state.seed);
+                    continue;
+                }
+                match event {
+                    Event::KeyDown{keycode: Some(Keycode::Up), ..} => {
+                        self.tuning_cursor = self.tuning_cursor.saturating_sub(1);
+                        continue;
+                    },
+                    Event::KeyDown{keycode: Some(Keycode::Down), ..} => {
+                        if self.tuning_cursor + 1 < self.gen_params.n_knobs() {
+                            self.tuning_cursor += 1;
+                        }
+                        continue;
+                    },
+                    Event::KeyDown{keycode: Some(Keycode::F3), ..} => {
+                        let path = self.gen_params_path.clone().unwrap_or("levelgen.txt".to_string());
+                        match self.gen_params.save(&path) {
+                            Ok(()) => println!("saved {}", path),
+                            Err(e) => println!("couldnt save levelgen params: {}", e),
+                        }
+                        continue;
+                    },
+                    _ => {},
+                }
+            }
+            if self.show_leaderboard {
+                let n_rows = self.leaderboard.best_per_seed().len();
+                match event {
+                    Event::KeyDown{keycode: Some(Keycode::Up), ..} => {
+                        self.leaderboard_cursor = self.leaderboard_cursor.saturating_sub(1);
+                    },
+                    Event::KeyDown{keycode: Some(Keycode::Down), ..} if self.leaderboard_cursor + 1 < n_rows => {
+                        self.leaderboard_cursor += 1;
+                    },
+                    Event::KeyDown{keycode: Some(Keycode::Return), ..} => {
+                        if let Some(run) = self.leaderboard.best_per_seed().get(self.leaderboard_cursor) {
+                            self.level = None;
+                            self.set_generator(Generator::Drunk, run.seed);
+                            self.show_leaderboard = false;
+                        }
+                    },
+                    _ => {},
+                }
+            }
             match event {
                 Event::Quit {..} |
                 Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {
@@ -119,41 +476,44 @@ impl Game {
                 Event::KeyDown{keycode: Some(Keycode::P), ..} => {
                     self.pause = !self.pause;
                 },
-                Event::KeyDown{keycode: Some(Keycode::Num1), ..} => {
-                    // may have to make a side effect
-                    if let Some(player) = self.state.entities.get_mut(&self.player_id) {
-                        player.gun = Gun::new_pistol();
-                    }
+                Event::KeyDown{keycode: Some(Keycode::Q), ..} => {
+                    self.frame_commands.push(Command::Throw(self.player_id));
                 },
-                Event::KeyDown{keycode: Some(Keycode::Num2), ..} => {
-                    // may have to make a side effect
-                    if let Some(player) = self.state.entities.get_mut(&self.player_id) {
-                        player.gun = Gun::new_makina();
-                    }
+                Event::KeyDown{keycode: Some(Keycode::E), ..} => {
+                    self.frame_commands.push(Command::Eat(self.player_id));
                 },
-                Event::KeyDown{keycode: Some(Keycode::Num3), ..} => {
-                    // may have to make a side effect
-                    if let Some(player) = self.state.entities.get_mut(&self.player_id) {
-                        player.gun = Gun::new_burst_rifle();
-                    }
+                Event::KeyDown{keycode: Some(Keycode::R), ..} => {
+                    self.restart(rand::thread_rng().gen());
                 },
-                Event::KeyDown{keycode: Some(Keycode::Num4), ..} => {
-                    // may have to make a side effect
-                    if let Some(player) = self.state.entities.get_mut(&self.player_id) {
-                        player.gun = Gun::new_shotgun();
+                Event::KeyDown{keycode: Some(Keycode::F5), ..} => {
+                    match self.state.save("quicksave.snapshot") {
+                        Ok(()) => println!("quicksaved"),
+                        Err(e) => println!("couldnt quicksave: {}", e),
                     }
                 },
-                Event::KeyDown{keycode: Some(Keycode::Num5), ..} => {
-                    // may have to make a side effect
-                    if let Some(player) = self.state.entities.get_mut(&self.player_id) {
-                        player.gun = Gun::new_scattergun();
+                Event::KeyDown{keycode: Some(Keycode::F9), ..} => {
+                    match SimulationState::load("quicksave.snapshot") {
+                        Ok(state) => self.load_state(state),
+                        Err(e) => println!("couldnt quickload: {}", e),
                     }
                 },
-                Event::KeyDown{keycode: Some(Keycode::R), ..} => {
-                    println!("===== reset =====");
-                    self.state = generate_level_drunk();
+                Event::KeyDown{keycode: Some(Keycode::G), ..} => {
+                    let i = ALL_GENERATORS.iter().position(|g| *g == self.generator).unwrap();
+                    let next = ALL_GENERATORS[(i + 1) % ALL_GENERATORS.len()];
+                    println!("generator: {}", next.name());
+                    self.set_generator(next, rand::thread_rng().gen());
+                },
+                Event::KeyDown{keycode: Some(Keycode::F2), ..} => {
+                    self.editing = !self.editing;
                     self.initialize();
                 },
+                Event::KeyDown{keycode: Some(Keycode::F4), ..} => {
+                    self.tuning = !self.tuning;
+                },
+                Event::KeyDown{keycode: Some(Keycode::L), ..} => {
+                    self.show_leaderboard = !self.show_leaderboard;
+                    self.leaderboard_cursor = 0;
+                },
                 _ => {},
             }
         }
@@ -182,12 +542,23 @@ impl Game {
         }
 
         // draw entities
+        let time = self.state.time as f32;
         let mut draw_entity = |entity: &Entity| {
             canvas.set_draw_color(entity.colour);
             let screenspace_rect = self.transform.project_rect(entity.aabb);
             let px_rect = self.transform.sdl_rect(screenspace_rect);
 
             canvas.fill_rect(px_rect).unwrap();
+
+            // eaten gun effects
+            if entity.is_shielded(time) {
+                canvas.set_draw_color(Color::RGB(0, 255, 255));
+                canvas.draw_rect(self.transform.sdl_rect(screenspace_rect.dilate(0.006))).unwrap();
+            }
+            if entity.is_boosted(time) {
+                canvas.set_draw_color(Color::RGB(255, 160, 0));
+                canvas.draw_rect(self.transform.sdl_rect(screenspace_rect.dilate(0.003))).unwrap();
+            }
         };
 
         self.state.entities.iter().filter(|(_, entity)| entity.draw_order == DrawOrder::Back).for_each(|(_, entity)| draw_entity(entity));
@@ -195,7 +566,7 @@ impl Game {
 
         // draw hud
         let player_hp_fraction = match self.state.entities.get(&self.player_id) {
-            Some(player) => {player.health / 5.0},
+            Some(player) => {player.health / PLAYER_MAX_HEALTH},
             None => 0.0,
         };
 
@@ -209,6 +580,17 @@ impl Game {
         canvas.set_draw_color(Color::RGB(255, 0, 0));
         canvas.fill_rect(self.transform.sdl_rect(hp_fg_rect)).unwrap();
 
+        // gun queue above the hp bar, whats out first with its ammo left
+        let pixel = 0.004;
+        for (i, gun) in self.state.guns(self.player_id).iter().enumerate() {
+            let slot = Rect::new(i as f32 * 0.065, 0.59, 0.06, 0.05);
+            canvas.set_draw_color(if i == 0 { Color::RGB(255, 255, 255) } else { Color::RGB(0, 0, 0) });
+            canvas.fill_rect(self.transform.sdl_rect(slot)).unwrap();
+            canvas.set_draw_color(if gun.ammo > 0 { Color::RGB(80, 80, 80) } else { Color::RGB(100, 0, 0) });
+            canvas.fill_rect(self.transform.sdl_rect(slot.dilate(-0.005))).unwrap();
+            draw_text(canvas, &self.transform, &gun.ammo.to_string(), slot.x + 0.01, slot.y + 0.015, pixel, Color::RGB(255, 255, 0));
+        }
+
 
         // draw minimap
         let mm_border = Rect::new(0.0, 0.7, 0.3, 0.3);
@@ -243,13 +625,160 @@ impl Game {
             canvas.set_draw_color(Color::RGB(255, 255, 255));
             canvas.fill_rect(self.transform.sdl_rect(player_rect)).unwrap();
         }
+
+        if self.editing {
+            self.draw_editor(canvas);
+        }
+
+        if self.tuning {
+            self.draw_tuning(canvas);
+        }
+
+        if self.show_leaderboard {
+            self.draw_leaderboard(canvas);
+        }
     }
 
+    // one row per knob: drunk walk in grey, noise in blue, spawn distance in orange, then spawn weights in the entitys colour
+    fn draw_tuning(&self, canvas: &mut Canvas<Window>) {
+        let bg_rect = Rect::new(self.transform.aspect_ratio() - 0.62, 0.02, 0.6, 0.04 + self.gen_params.n_knobs() as f32 * 0.04);
+        canvas.set_draw_color(Color::RGB(20, 20, 40));
+        canvas.fill_rect(self.transform.sdl_rect(bg_rect)).unwrap();
+
+        let pixel = 0.005;
+        for knob in 0..self.gen_params.n_knobs() {
+            let y = bg_rect.y + 0.02 + knob as f32 * 0.04;
+            if knob == self.tuning_cursor {
+                canvas.set_draw_color(Color::RGB(60, 60, 100));
+                canvas.fill_rect(self.transform.sdl_rect(Rect::new(bg_rect.x, y - 0.0065, bg_rect.w, 0.038))).unwrap();
+            }
+
+            let swatch_colour = if knob < 5 {
+                Color::RGB(128, 128, 128)
+            } else if knob < 9 {
+                Color::RGB(80, 120, 255)
+            } else if knob < N_SCALAR_KNOBS {
+                Color::RGB(255, 160, 0)
+            } else {
+                let (kind, _) = self.gen_params.spawn_table[knob - N_SCALAR_KNOBS];
+                char_for_entity(kind).and_then(|c| entity_for_char(c, 0.0, 0.0)).map(|e| e.colour.into()).unwrap_or(Color::RGB(255, 0, 255))
+            };
+            canvas.set_draw_color(swatch_colour);
+            canvas.fill_rect(self.transform.sdl_rect(Rect::new(bg_rect.x + 0.02, y, 5.0 * pixel, 5.0 * pixel))).unwrap();
+
+            let white = Color::RGB(255, 255, 255);
+            draw_text(canvas, &self.transform, &self.gen_params.knob_label(knob), bg_rect.x + 0.06, y, pixel, white);
+            draw_text(canvas, &self.transform, &format!("{:.2}", self.gen_params.knob_value(knob)), bg_rect.x + 0.45, y, pixel, white);
+        }
+    }
+
+    // outline the tile under the mouse in the brush colour, and list the brushes
+    fn draw_editor(&self, canvas: &mut Canvas<Window>) {
+        let brush_colour = |c: char| match c {
+            '.' => Color::RGB(200, 200, 100),
+            '#' => Color::RGB(50, 50, 100),
+            c => entity_for_char(c, 0.0, 0.0).map(|e| e.colour.into()).unwrap_or(Color::RGB(255, 0, 255)),
+        };
+
+        let (x, y) = self.state.terrain.get_xy_of_position(self.editor_cursor);
+        let tile_rect = self.transform.project_rect(self.state.terrain.get_rect_2d(x, y));
+        canvas.set_draw_color(brush_colour(self.editor_brush));
+        canvas.draw_rect(self.transform.sdl_rect(tile_rect)).unwrap();
+        canvas.draw_rect(self.transform.sdl_rect(tile_rect.dilate(-0.004))).unwrap();
+
+        for (i, c) in EDITOR_BRUSHES.iter().enumerate() {
+            let swatch = Rect::new(0.02 + i as f32 * 0.06, 0.02, 0.05, 0.05);
+            if *c == self.editor_brush {
+                canvas.set_draw_color(Color::RGB(255, 255, 255));
+                canvas.fill_rect(self.transform.sdl_rect(swatch.dilate(0.005))).unwrap();
+            }
+            canvas.set_draw_color(brush_colour(*c));
+            canvas.fill_rect(self.transform.sdl_rect(swatch)).unwrap();
+            if i < 9 {
+                draw_text(canvas, &self.transform, &(i + 1).to_string(), swatch.x + 0.016, swatch.y + 0.06, 0.004, Color::RGB(0, 0, 0));
+            }
+        }
+    }
+
+    // columns: completed, seed, time, kills, damage taken, seed rating
+    fn draw_leaderboard(&self, canvas: &mut Canvas<Window>) {
+        let bg_rect = Rect::new(0.1, 0.05, self.transform.aspect_ratio() - 0.2, 0.9);
+        canvas.set_draw_color(Color::RGB(20, 20, 40));
+        canvas.fill_rect(self.transform.sdl_rect(bg_rect)).unwrap();
+
+        let pixel = 0.006;
+        let row_h = 0.05;
+        let max_rows = 16;
+        let rows = self.leaderboard.best_per_seed();
+        let first_row = self.leaderboard_cursor.saturating_sub(max_rows - 1);
+
+        for (i, run) in rows.iter().enumerate().skip(first_row).take(max_rows) {
+            let y = bg_rect.y + 0.04 + (i - first_row) as f32 * row_h;
+            if i == self.leaderboard_cursor {
+                canvas.set_draw_color(Color::RGB(60, 60, 100));
+                canvas.fill_rect(self.transform.sdl_rect(Rect::new(bg_rect.x, y - 0.01, bg_rect.w, row_h))).unwrap();
+            }
+
+            canvas.set_draw_color(if run.completed { Color::RGB(0, 255, 0) } else { Color::RGB(255, 0, 0) });
+            canvas.fill_rect(self.transform.sdl_rect(Rect::new(bg_rect.x + 0.03, y, 5.0 * pixel, 5.0 * pixel))).unwrap();
+
+            let white = Color::RGB(255, 255, 255);
+            draw_text(canvas, &self.transform, &run.seed.to_string(), bg_rect.x + 0.1, y, pixel, white);
+            draw_text(canvas, &self.transform, &format!("{:.2}", run.time), bg_rect.x + 0.6, y, pixel, white);
+            draw_text(canvas, &self.transform, &run.kills.to_string(), bg_rect.x + 0.8, y, pixel, white);
+            draw_text(canvas, &self.transform, &format!("{:.1}", run.damage_taken), bg_rect.x + 0.95, y, pixel, white);
+            draw_text(canvas, &self.transform, &format!("{:.0}", self.ratings.seed(run.seed).rating), bg_rect.x + 1.15, y, pixel, white);
+        }
+
+        // own rating in the corner
+        let player_rating = format!("{:.0}", self.ratings.player(&self.profile).rating);
+        draw_text(canvas, &self.transform, &player_rating, bg_rect.right() - 0.2, bg_rect.bot() - 0.06, pixel, Color::RGB(255, 255, 0));
+    }
+
+}
+
+impl Game {
     pub fn update(&mut self, dt: f64) {
-        if self.pause { return; }
-        self.state.dt = dt;
+        if self.pause || self.show_leaderboard || self.editing || self.tuning { return; }
 
-        compute_ai_commands(&self.state, &mut self.frame_commands);
+        // edits cant be replayed as commands, so the recording carries on from the edited state
+        if self.unrecorded_edits {
+            self.recording = self.new_recording(self.state.seed);
+            self.recording.snapshot = Some(self.state.serialize());
+            self.unrecorded_edits = false;
+        }
+
+        let mut dt = dt;
+        if let Some((replay, next_frame)) = &mut self.playback {
+            // recorded commands already include the ai's
+            match replay.frames.get(*next_frame) {
+                Some(frame) => {
+                    dt = frame.dt;
+                    self.frame_commands.clear();
+                    self.frame_commands.extend_from_slice(&frame.commands);
+                    *next_frame += 1;
+                },
+                None => {
+                    println!("replay finished");
+                    self.playback = None;
+                    return;
+                },
+            }
+        } else {
+            let player_tile = match self.state.entities.get(&self.player_id) {
+                Some(player) => self.state.terrain.get_xy_of_position(player.aabb.center()),
+                None => (-1, -1),
+            };
+            if self.flow_field.as_ref().map(|f| f.origin) != Some(player_tile) {
+                self.flow_field = Some(FlowField::new(&self.state.terrain, player_tile));
+            }
+            let terrain = &self.state.terrain;
+            let pathfinder = self.pathfinder.get_or_insert_with(|| Pathfinder::new(terrain));
+            compute_ai_commands(&self.state, self.flow_field.as_ref().unwrap(), pathfinder, &mut self.frame_commands);
+        }
+        self.recording.record_frame(dt, &self.frame_commands);
+
+        self.state.dt = dt;
 
         for command in self.frame_commands.iter() {
             apply_command(&mut self.state, *command);
@@ -257,7 +786,7 @@ impl Game {
 
         self.state.time += dt;
 
-        simulate_entity_entity_collisions(&self.state.entities, &mut self.frame_collisions, dt as f32);
+        simulate_entity_entity_collisions(&self.state.entities, &mut self.frame_collisions, dt as f32, self.state.terrain.elem_w);
         simulate_entity_terrain_collisions(&self.state.entities, &self.state.terrain, &mut self.frame_collisions, dt as f32);
 
         for col in self.frame_collisions.iter() {
@@ -270,7 +799,7 @@ impl Game {
         }
 
         handle_melee_damage(&self.state, &self.frame_collisions, &mut self.frame_side_effects);
-        compute_movement(&self.state.entities, &self.frame_collisions, &mut self.frame_movements, dt as f32);
+        compute_movement(&self.state.entities, &self.state.terrain, &self.frame_collisions, &mut self.frame_movements, dt as f32);
 
         // apply movements: a bit oldschool and maybe silly
         for (entity_id, dx, dy) in self.frame_movements.iter() {
@@ -280,11 +809,31 @@ impl Game {
         }
 
         handle_bullet_impacts(&self.state, &self.frame_collisions, &mut self.frame_side_effects);
+        handle_thrown_gun_impacts(&self.state, &self.frame_collisions, &mut self.frame_side_effects);
+        handle_pickups(&self.state, &self.frame_collisions, &mut self.frame_side_effects);
+
+        let player_health = self.state.entities.get(&self.player_id).map(|p| p.health);
 
         for effect in self.frame_side_effects.iter() {
             self.state.resolve_side_effect(*effect);
         }
 
-        self.state.entities.retain(|_, e| e.health > 0.0);
+        // run stats
+        if let (Some(before), Some(player)) = (player_health, self.state.entities.get(&self.player_id)) {
+            self.damage_taken += (before - player.health).max(0.0);
+        }
+        self.kills += self.state.entities.values()
+            .filter(|e| e.health <= 0.0 && e.force == EntityForce::Enemy && e.variety != EntityType::Bullet)
+            .count() as u32;
+
+        self.state.retain_entities(|_, e| e.health > 0.0);
+
+        if !self.run_over {
+            if !self.state.entities.contains_key(&self.player_id) {
+                self.finish_run(false);
+            } else if !self.state.entities.values().any(|e| e.force == EntityForce::Enemy && e.variety != EntityType::Bullet) {
+                self.finish_run(true);
+            }
+        }
     }
 }
\ No newline at end of file
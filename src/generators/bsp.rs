@@ -0,0 +1,191 @@
+use crate::grid::*;
+use crate::simulation_state::*;
+use crate::generators::params::*;
+
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/*
+rooms and corridors: split the map in two over and over (bsp), put a room in each leaf,
+then join the two halves of every split with a corridor.
+the rooms and which ones are joined come back out so encounters can be placed on purpose
+*/
+
+// in tiles
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Room {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Room {
+    pub fn center(&self) -> (i32, i32) {
+        (self.x + self.w/2, self.y + self.h/2)
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.x + self.w && y >= self.y && y < self.y + self.h
+    }
+
+    pub fn area(&self) -> i32 {
+        self.w * self.h
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoomLayout {
+    pub rooms: Vec<Room>,
+    pub corridors: Vec<(usize, usize)>,     // indices into rooms
+}
+
+impl RoomLayout {
+    pub fn neighbours(&self, room: usize) -> Vec<usize> {
+        self.corridors.iter().filter_map(|&(a, b)| {
+            if a == room { Some(b) } else if b == room { Some(a) } else { None }
+        }).collect()
+    }
+}
+
+const MIN_LEAF: i32 = 8;
+const MIN_ROOM: i32 = 3;
+
+// returns indices of the rooms made under this node
+fn split(rng: &mut ChaCha8Rng, leaf: Room, layout: &mut RoomLayout) -> Vec<usize> {
+    let can_split_x = leaf.w >= MIN_LEAF * 2;
+    let can_split_y = leaf.h >= MIN_LEAF * 2;
+
+    if !can_split_x && !can_split_y {
+        // room somewhere inside, leaving a wall on every side
+        let w = rng.gen_range(MIN_ROOM..leaf.w - 1);
+        let h = rng.gen_range(MIN_ROOM..leaf.h - 1);
+        let x = leaf.x + rng.gen_range(1..leaf.w - w);
+        let y = leaf.y + rng.gen_range(1..leaf.h - h);
+        layout.rooms.push(Room {x: x, y: y, w: w, h: h});
+        return vec![layout.rooms.len() - 1];
+    }
+
+    // split along the long side
+    let split_x = if can_split_x && can_split_y { leaf.w >= leaf.h } else { can_split_x };
+    let (a, b) = if split_x {
+        let at = rng.gen_range(MIN_LEAF..=leaf.w - MIN_LEAF);
+        (Room {x: leaf.x, y: leaf.y, w: at, h: leaf.h}, Room {x: leaf.x + at, y: leaf.y, w: leaf.w - at, h: leaf.h})
+    } else {
+        let at = rng.gen_range(MIN_LEAF..=leaf.h - MIN_LEAF);
+        (Room {x: leaf.x, y: leaf.y, w: leaf.w, h: at}, Room {x: leaf.x, y: leaf.y + at, w: leaf.w, h: leaf.h - at})
+    };
+
+    let rooms_a = split(rng, a, layout);
+    let rooms_b = split(rng, b, layout);
+
+    // join the closest pair across the split
+    let dist = |i: usize, j: usize| {
+        let (ax, ay) = layout.rooms[i].center();
+        let (bx, by) = layout.rooms[j].center();
+        (ax - bx).abs() + (ay - by).abs()
+    };
+    let mut best = (rooms_a[0], rooms_b[0]);
+    for &i in rooms_a.iter() {
+        for &j in rooms_b.iter() {
+            if dist(i, j) < dist(best.0, best.1) {
+                best = (i, j);
+            }
+        }
+    }
+    layout.corridors.push(best);
+
+    rooms_a.into_iter().chain(rooms_b).collect()
+}
+
+fn carve_corridor(g: &mut Grid, from: (i32, i32), to: (i32, i32), horizontal_first: bool) {
+    let corner = if horizontal_first { (to.0, from.1) } else { (from.0, to.1) };
+    for &(a, b) in [(from, corner), (corner, to)].iter() {
+        for x in a.0.min(b.0)..=a.0.max(b.0) {
+            for y in a.1.min(b.1)..=a.1.max(b.1) {
+                g.set_2d(x, y, Tile::Ground);
+            }
+        }
+    }
+}
+
+pub fn generate_level_bsp(seed: u64, params: &LevelGenParams) -> (SimulationState, RoomLayout) {
+    let side_length = params.side_length;
+    let elem_size = params.elem_size;
+    let spawns_per_tile = 1.0 / 12.0;
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut g = Grid::new(side_length, side_length, elem_size, elem_size);
+    let mut layout = RoomLayout {rooms: Vec::new(), corridors: Vec::new()};
+
+    split(&mut rng, Room {x: 0, y: 0, w: side_length, h: side_length}, &mut layout);
+
+    for room in layout.rooms.iter() {
+        for x in room.x..room.x + room.w {
+            for y in room.y..room.y + room.h {
+                g.set_2d(x, y, Tile::Ground);
+            }
+        }
+    }
+    for &(a, b) in layout.corridors.iter() {
+        let horizontal_first = rng.gen_range(0..2) == 0;
+        carve_corridor(&mut g, layout.rooms[a].center(), layout.rooms[b].center(), horizontal_first);
+    }
+
+    // player starts in the first room, encounters go in all the others
+    let player_tile = layout.rooms[0].center();
+    let mut spawn_tiles = Vec::new();
+    for room in layout.rooms.iter().skip(1) {
+        let n = ((room.area() as f32 * spawns_per_tile).ceil() as i32).max(1);
+        for _ in 0..n {
+            spawn_tiles.push((rng.gen_range(room.x..room.x + room.w), rng.gen_range(room.y..room.y + room.h)));
+        }
+    }
+    spawn_tiles.sort();
+    spawn_tiles.dedup();
+
+    (populate_level(g, player_tile, &spawn_tiles, &[], params, rng, seed), layout)
+}
+
+#[test]
+fn test_bsp() {
+    use crate::entity::*;
+
+    for seed in 0..20 {
+        let (state, layout) = generate_level_bsp(seed, &LevelGenParams::default());
+        let g = &state.terrain;
+        assert!(layout.rooms.len() >= 4);
+
+        // a tree of corridors joins every room
+        assert_eq!(layout.corridors.len(), layout.rooms.len() - 1);
+        let mut seen = vec![false; layout.rooms.len()];
+        let mut stack = vec![0];
+        while let Some(r) = stack.pop() {
+            if seen[r] { continue; }
+            seen[r] = true;
+            stack.extend(layout.neighbours(r));
+        }
+        assert!(seen.iter().all(|s| *s));
+
+        // rooms dont overlap and are inside the border
+        for (i, a) in layout.rooms.iter().enumerate() {
+            assert!(a.x >= 1 && a.y >= 1 && a.x + a.w < g.w && a.y + a.h < g.h);
+            for b in layout.rooms.iter().skip(i + 1) {
+                assert!(a.x + a.w <= b.x || b.x + b.w <= a.x || a.y + a.h <= b.y || b.y + b.h <= a.y);
+            }
+        }
+
+        // and its all walkable from the player
+        let player = state.entities.values().find(|e| e.variety == EntityType::Player).unwrap();
+        let (px, py) = g.get_xy_of_position(player.aabb.center());
+        assert!(layout.rooms[0].contains(px, py));
+        let n_ground = g.tiles.iter().filter(|t| **t == Tile::Ground).count();
+        assert_eq!(g.flood_fill(px, py).len(), n_ground);
+    }
+
+    // size comes from the settings
+    let params = LevelGenParams {side_length: 30, elem_size: 0.15, ..LevelGenParams::default()};
+    let g = generate_level_bsp(3, &params).0.terrain;
+    assert_eq!((g.w, g.h, g.elem_w, g.elem_h), (30, 30, 0.15, 0.15));
+}
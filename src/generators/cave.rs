@@ -0,0 +1,147 @@
+use crate::grid::*;
+use crate::simulation_state::*;
+use crate::generators::params::*;
+
+use rand::Rng;
+use rand::SeedableRng;
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
+
+/*
+caves: random noise smoothed by a cellular automaton a few times,
+then only the biggest connected cave is kept so everything is reachable
+*/
+
+fn count_wall_neighbours(g: &Grid, x: i32, y: i32) -> i32 {
+    let mut n = 0;
+    for i in -1..1+1 {
+        for j in -1..1+1 {
+            if i == 0 && j == 0 { continue; }
+            // off the edge counts as wall so caves close up at the border
+            if g.get_2d(x + i, y + j) != Some(Tile::Ground) {
+                n += 1;
+            }
+        }
+    }
+    n
+}
+
+pub fn smooth_cave(g: &Grid) -> Grid {
+    let mut next = Grid::new(g.w, g.h, g.elem_w, g.elem_h);
+    for x in 1..g.w-1 {
+        for y in 1..g.h-1 {
+            let walls = count_wall_neighbours(g, x, y);
+            let t = if walls > 4 {
+                Tile::Wall
+            } else if walls < 4 {
+                Tile::Ground
+            } else {
+                g.get_2d(x, y).unwrap()
+            };
+            next.set_2d(x, y, t);
+        }
+    }
+    next
+}
+
+// fills in every ground tile not in the biggest region, returns the biggest region
+pub fn keep_largest_region(g: &mut Grid) -> Vec<(i32, i32)> {
+    let mut seen = vec![false; g.tiles.len()];
+    let mut largest: Vec<(i32, i32)> = Vec::new();
+
+    for y in 0..g.h {
+        for x in 0..g.w {
+            if seen[(x + y * g.w) as usize] || g.get_2d(x, y) != Some(Tile::Ground) { continue; }
+            let region = g.flood_fill(x, y);
+            for (rx, ry) in region.iter() {
+                seen[(rx + ry * g.w) as usize] = true;
+            }
+            if region.len() > largest.len() {
+                largest = region;
+            }
+        }
+    }
+
+    for y in 0..g.h {
+        for x in 0..g.w {
+            g.set_2d(x, y, Tile::Wall);
+        }
+    }
+    for (x, y) in largest.iter() {
+        g.set_2d(*x, *y, Tile::Ground);
+    }
+    largest
+}
+
+pub fn generate_level_cave(seed: u64, params: &LevelGenParams) -> SimulationState {
+    let side_length = params.side_length;
+    let elem_size = params.elem_size;
+    let p_wall = 0.45;
+    let smooth_iters = 5;
+    let num_spawns = 40;
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let mut g = Grid::new(side_length, side_length, elem_size, elem_size);
+    for x in 1..side_length-1 {
+        for y in 1..side_length-1 {
+            if rng.gen_range(0.0..1.0) >= p_wall {
+                g.set_2d(x, y, Tile::Ground);
+            }
+        }
+    }
+
+    for _ in 0..smooth_iters {
+        g = smooth_cave(&g);
+    }
+
+    let mut cave = keep_largest_region(&mut g);
+    if cave.is_empty() {
+        // basically never happens but dont want a level with nowhere to stand
+        let mid = (side_length/2, side_length/2);
+        g.set_2d(mid.0, mid.1, Tile::Ground);
+        cave.push(mid);
+    }
+
+    cave.shuffle(&mut rng);
+    let player_tile = cave[0];
+    let spawn_tiles: Vec<(i32, i32)> = far_enough_from(&g, player_tile, &cave, params.min_spawn_distance)
+        .into_iter()
+        .take(num_spawns)
+        .collect();
+
+    populate_level(g, player_tile, &spawn_tiles, &[], params, rng, seed)
+}
+
+#[test]
+fn test_cave() {
+    use crate::entity::*;
+
+    for seed in 0..20 {
+        let state = generate_level_cave(seed, &LevelGenParams::default());
+        let g = &state.terrain;
+
+        // one connected cave
+        let player = state.entities.values().find(|e| e.variety == EntityType::Player).unwrap();
+        let (px, py) = g.get_xy_of_position(player.aabb.center());
+        let n_ground = g.tiles.iter().filter(|t| **t == Tile::Ground).count();
+        assert_eq!(g.flood_fill(px, py).len(), n_ground);
+
+        // closed border
+        for i in 0..g.w {
+            assert_eq!(g.get_2d(i, 0), Some(Tile::Wall));
+            assert_eq!(g.get_2d(0, i), Some(Tile::Wall));
+            assert_eq!(g.get_2d(i, g.h-1), Some(Tile::Wall));
+            assert_eq!(g.get_2d(g.w-1, i), Some(Tile::Wall));
+        }
+
+        for e in state.entities.values() {
+            assert_eq!(g.get_position(e.aabb.center()), Some(Tile::Ground));
+        }
+    }
+
+    // size comes from the settings
+    let params = LevelGenParams {side_length: 30, elem_size: 0.15, ..LevelGenParams::default()};
+    let g = generate_level_cave(3, &params).terrain;
+    assert_eq!((g.w, g.h, g.elem_w, g.elem_h), (30, 30, 0.15, 0.15));
+}
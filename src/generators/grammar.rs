@@ -0,0 +1,323 @@
+use crate::grid::*;
+use crate::entity::*;
+use crate::simulation_state::*;
+use crate::generators::params::*;
+use crate::generators::bsp::Room;
+
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/*
+levels from a mission graph instead of pure noise, so there is some pacing.
+
+start with   Start -> Chain -> Lock -> Chain -> Exit
+and rewrite  Chain -> Combat -> Chain   (keeps going for a while)
+             Chain -> Combat            (stops)
+then hang    Key (maybe behind a Combat) off something before the Lock
+
+each node gets a cell on a coarse grid next to its parent and a room inside that cell,
+edges become corridors. the lock is a door plugging the only way through, it opens when
+the player picks up the key, which has a bigdog guarding it. theres always an enemy at the exit too,
+the spawn table might only put crates and guns there
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissionNode {
+    Start,
+    Chain,  // non terminal
+    Combat,
+    Key,
+    Lock,
+    Exit,
+}
+
+// a tree, edges go parent -> child
+#[derive(Clone, Debug, PartialEq)]
+pub struct MissionGraph {
+    pub nodes: Vec<MissionNode>,
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl MissionGraph {
+    pub fn axiom() -> MissionGraph {
+        MissionGraph {
+            nodes: vec![MissionNode::Start, MissionNode::Chain, MissionNode::Lock, MissionNode::Chain, MissionNode::Exit],
+            edges: vec![(0, 1), (1, 2), (2, 3), (3, 4)],
+        }
+    }
+
+    pub fn children(&self, node: usize) -> Vec<usize> {
+        self.edges.iter().filter(|(a, _)| *a == node).map(|(_, b)| *b).collect()
+    }
+
+    pub fn parent(&self, node: usize) -> Option<usize> {
+        self.edges.iter().find(|(_, b)| *b == node).map(|(a, _)| *a)
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        !self.nodes.contains(&MissionNode::Chain)
+    }
+
+    // splits node -> child into node -> new -> child
+    fn insert_after(&mut self, node: usize, kind: MissionNode) -> usize {
+        self.nodes.push(kind);
+        let new = self.nodes.len() - 1;
+        for edge in self.edges.iter_mut() {
+            if edge.0 == node {
+                edge.0 = new;
+            }
+        }
+        self.edges.push((node, new));
+        new
+    }
+
+    fn add_child(&mut self, node: usize, kind: MissionNode) -> usize {
+        self.nodes.push(kind);
+        let new = self.nodes.len() - 1;
+        self.edges.push((node, new));
+        new
+    }
+
+    pub fn rewrite(&mut self, rng: &mut impl Rng, p_continue: f32, max_chain: i32) {
+        while let Some(i) = self.nodes.iter().position(|n| *n == MissionNode::Chain) {
+            let mut length = 1;
+            self.nodes[i] = MissionNode::Combat;
+            let mut current = i;
+            while length < max_chain && rng.gen_range(0.0..1.0) < p_continue {
+                current = self.insert_after(current, MissionNode::Combat);
+                length += 1;
+            }
+        }
+
+        // key goes somewhere before the lock
+        let lock = self.nodes.iter().position(|n| *n == MissionNode::Lock).unwrap();
+        let mut before_lock = Vec::new();
+        let mut node = self.parent(lock);
+        while let Some(n) = node {
+            before_lock.push(n);
+            node = self.parent(n);
+        }
+        let attach = before_lock[rng.gen_range(0..before_lock.len())];
+        let key_parent = if rng.gen_range(0..2) == 0 {
+            self.add_child(attach, MissionNode::Combat)
+        } else {
+            attach
+        };
+        self.add_child(key_parent, MissionNode::Key);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MissionLayout {
+    pub graph: MissionGraph,
+    pub cells: Vec<(i32, i32)>,  // per node, on the coarse grid
+    pub rooms: Vec<Room>,        // per node, in tiles
+}
+
+const CELL_SIZE: i32 = 6;
+const MIN_CELLS: i32 = 5;  // the fallback layout is 5 across
+const MAX_LAYOUT_TRIES: usize = 100;
+
+// each node next to its parent, None if it painted itself into a corner
+fn place_cells(graph: &MissionGraph, n_cells: i32, rng: &mut impl Rng) -> Option<Vec<(i32, i32)>> {
+    let mut cells: Vec<Option<(i32, i32)>> = vec![None; graph.nodes.len()];
+    cells[0] = Some((rng.gen_range(0..n_cells), rng.gen_range(0..n_cells)));
+
+    let mut queue = vec![0];
+    while !queue.is_empty() {
+        let node = queue.remove(0);
+        let (px, py) = cells[node].unwrap();
+        for child in graph.children(node) {
+            let free: Vec<(i32, i32)> = [(1, 0), (-1, 0), (0, 1), (0, -1)].iter()
+                .map(|(dx, dy)| (px + dx, py + dy))
+                .filter(|(x, y)| *x >= 0 && *y >= 0 && *x < n_cells && *y < n_cells)
+                .filter(|c| !cells.contains(&Some(*c)))
+                .collect();
+            if free.is_empty() {
+                return None;
+            }
+            cells[child] = Some(free[rng.gen_range(0..free.len())]);
+            queue.push(child);
+        }
+    }
+    Some(cells.into_iter().map(|c| c.unwrap()).collect())
+}
+
+// along the top row with the key under the first fight, for when the rewrites keep not fitting
+fn fallback_layout() -> (MissionGraph, Vec<(i32, i32)>) {
+    let graph = MissionGraph {
+        nodes: vec![MissionNode::Start, MissionNode::Combat, MissionNode::Lock, MissionNode::Combat, MissionNode::Exit, MissionNode::Key],
+        edges: vec![(0, 1), (1, 2), (2, 3), (3, 4), (1, 5)],
+    };
+    (graph, vec![(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (1, 1)])
+}
+
+// tile bounds of a cell, the outer ring of the map is left as wall
+fn cell_rect(cell: (i32, i32)) -> Room {
+    Room {x: 1 + cell.0 * CELL_SIZE, y: 1 + cell.1 * CELL_SIZE, w: CELL_SIZE, h: CELL_SIZE}
+}
+
+// move across first (inside the other cell), then straight into target along the line between the cells.
+// so corridors into a lock only ever arrive head on and dont meet anywhere but the door
+fn carve_corridor_into(g: &mut Grid, other: (i32, i32), target: (i32, i32), along_x: bool) {
+    let corner = if along_x { (other.0, target.1) } else { (target.0, other.1) };
+    for &(a, b) in [(other, corner), (corner, target)].iter() {
+        for x in a.0.min(b.0)..=a.0.max(b.0) {
+            for y in a.1.min(b.1)..=a.1.max(b.1) {
+                g.set_2d(x, y, Tile::Ground);
+            }
+        }
+    }
+}
+
+pub fn generate_level_grammar(seed: u64, params: &LevelGenParams) -> (SimulationState, MissionLayout) {
+    // as many whole cells as fit in side_length, plus the outer wall ring
+    let n_cells = ((params.side_length - 2) / CELL_SIZE).max(MIN_CELLS);
+    let side_length = n_cells * CELL_SIZE + 2;
+    let elem_size = params.elem_size;
+    let p_continue = 0.6;
+    let max_chain = 4;
+    let spawns_per_tile = 1.0 / 4.0;
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let layout = (0..MAX_LAYOUT_TRIES).find_map(|_| {
+        let mut graph = MissionGraph::axiom();
+        graph.rewrite(&mut rng, p_continue, max_chain);
+        place_cells(&graph, n_cells, &mut rng).map(|cells| (graph, cells))
+    });
+    let (graph, cells) = layout.unwrap_or_else(fallback_layout);
+
+    let mut g = Grid::new(side_length, side_length, elem_size, elem_size);
+
+    // rooms keep a wall ring inside their cell so neighbouring cells only meet through corridors
+    let rooms: Vec<Room> = graph.nodes.iter().zip(cells.iter()).map(|(node, cell)| {
+        let c = cell_rect(*cell);
+        if *node == MissionNode::Lock {
+            let (x, y) = c.center();
+            return Room {x: x, y: y, w: 1, h: 1};
+        }
+        let w = rng.gen_range(3..=CELL_SIZE - 2);
+        let h = rng.gen_range(3..=CELL_SIZE - 2);
+        Room {
+            x: c.x + rng.gen_range(1..=CELL_SIZE - 1 - w),
+            y: c.y + rng.gen_range(1..=CELL_SIZE - 1 - h),
+            w: w,
+            h: h,
+        }
+    }).collect();
+
+    for room in rooms.iter() {
+        for x in room.x..room.x + room.w {
+            for y in room.y..room.y + room.h {
+                g.set_2d(x, y, Tile::Ground);
+            }
+        }
+    }
+    for &(parent, child) in graph.edges.iter() {
+        let along_x = cells[parent].1 == cells[child].1;
+        let (other, target) = if graph.nodes[parent] == MissionNode::Lock { (child, parent) } else { (parent, child) };
+        carve_corridor_into(&mut g, rooms[other].center(), rooms[target].center(), along_x);
+    }
+
+    let mut spawn_tiles = Vec::new();
+    for (node, room) in graph.nodes.iter().zip(rooms.iter()) {
+        if *node != MissionNode::Combat && *node != MissionNode::Exit { continue; }
+        let n = ((room.area() as f32 * spawns_per_tile).ceil() as i32).max(1);
+        for _ in 0..n {
+            spawn_tiles.push((rng.gen_range(room.x..room.x + room.w), rng.gen_range(room.y..room.y + room.h)));
+        }
+    }
+    spawn_tiles.sort();
+    spawn_tiles.dedup();
+
+    let mut fixtures = Vec::new();
+    for (node, room) in graph.nodes.iter().zip(rooms.iter()) {
+        let pos = g.get_rect_2d(room.center().0, room.center().1).center();
+        match node {
+            MissionNode::Lock => fixtures.push(Entity::new_door(pos.x, pos.y, elem_size)),
+            MissionNode::Key => {
+                fixtures.push(Entity::new_key(pos.x, pos.y));
+                let guard = g.get_rect_2d(room.x, room.y).center();
+                fixtures.push(Entity::new_bigdog(guard.x, guard.y));
+            },
+            MissionNode::Exit => fixtures.push(Entity::new_enemy(pos.x, pos.y)),
+            _ => {},
+        }
+    }
+
+    let state = populate_level(g, rooms[0].center(), &spawn_tiles, &fixtures, params, rng, seed);
+
+    (state, MissionLayout {graph: graph, cells: cells, rooms: rooms})
+}
+
+#[test]
+fn test_mission_graph() {
+    let mut rng = ChaCha8Rng::seed_from_u64(3);
+    for _ in 0..50 {
+        let mut graph = MissionGraph::axiom();
+        graph.rewrite(&mut rng, 0.6, 4);
+        assert!(graph.is_terminal());
+        assert_eq!(graph.edges.len(), graph.nodes.len() - 1);
+
+        let count = |kind| graph.nodes.iter().filter(|n| **n == kind).count();
+        assert_eq!(count(MissionNode::Start), 1);
+        assert_eq!(count(MissionNode::Key), 1);
+        assert_eq!(count(MissionNode::Lock), 1);
+        assert_eq!(count(MissionNode::Exit), 1);
+
+        // key is reached before the lock
+        let key = graph.nodes.iter().position(|n| *n == MissionNode::Key).unwrap();
+        let mut node = Some(key);
+        while let Some(n) = node {
+            assert_ne!(graph.nodes[n], MissionNode::Lock);
+            node = graph.parent(n);
+        }
+    }
+}
+
+#[test]
+fn test_grammar_level() {
+    for seed in 0..20 {
+        let (state, layout) = generate_level_grammar(seed, &LevelGenParams::default());
+        let g = &state.terrain;
+        let tile_of = |kind| layout.rooms[layout.graph.nodes.iter().position(|n| *n == kind).unwrap()].center();
+        let start = tile_of(MissionNode::Start);
+
+        // everything connected
+        let n_ground = g.tiles.iter().filter(|t| **t == Tile::Ground).count();
+        assert_eq!(g.flood_fill(start.0, start.1).len(), n_ground);
+
+        // but only through the door
+        let mut shut = Grid::new(g.w, g.h, g.elem_w, g.elem_h);
+        shut.tiles = g.tiles.clone();
+        let lock = tile_of(MissionNode::Lock);
+        shut.set_2d(lock.0, lock.1, Tile::Wall);
+        let reachable = shut.flood_fill(start.0, start.1);
+        assert!(!reachable.contains(&tile_of(MissionNode::Exit)));
+        assert!(reachable.contains(&tile_of(MissionNode::Key)));
+
+        // a door in the doorway, the key on the near side and something waiting at the exit
+        let room_of = |kind| layout.rooms[layout.graph.nodes.iter().position(|n| *n == kind).unwrap()];
+        let in_room = |e: &Entity, kind| { let (x, y) = g.get_xy_of_position(e.aabb.center()); room_of(kind).contains(x, y) };
+        assert!(state.entities.values().any(|e| e.variety == EntityType::Door && g.get_xy_of_position(e.aabb.center()) == lock));
+        assert!(state.entities.values().any(|e| e.variety == EntityType::Key && in_room(e, MissionNode::Key)));
+        assert!(state.entities.values().any(|e| e.force == EntityForce::Enemy && in_room(e, MissionNode::Exit)));
+    }
+
+    // side_length picks the number of cells
+    for &(side_length, expected) in [(40, 38), (60, 56), (8, 32)].iter() {
+        let (state, _) = generate_level_grammar(1, &LevelGenParams {side_length: side_length, ..LevelGenParams::default()});
+        assert_eq!((state.terrain.w, state.terrain.h), (expected, expected));
+    }
+
+    // the fallback is a proper layout too
+    let (graph, cells) = fallback_layout();
+    for &(parent, child) in graph.edges.iter() {
+        let (a, b) = (cells[parent], cells[child]);
+        assert_eq!((a.0 - b.0).abs() + (a.1 - b.1).abs(), 1);
+    }
+    let key = graph.nodes.iter().position(|n| *n == MissionNode::Key).unwrap();
+    assert!(graph.parent(key).map(|p| graph.nodes[p] != MissionNode::Lock).unwrap());
+}
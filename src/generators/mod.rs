@@ -0,0 +1,56 @@
+pub mod cave;
+pub mod bsp;
+pub mod grammar;
+pub mod noise;
+pub mod params;
+pub mod placement;
+
+use crate::simulation_state::*;
+use crate::generators::cave::*;
+use crate::generators::bsp::*;
+use crate::generators::grammar::*;
+use crate::generators::noise::*;
+use crate::generators::params::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Generator {
+    Drunk,
+    Cave,
+    Bsp,
+    Grammar,
+    Noise,
+    Mixed,  // picks one of the others from the seed, so each floor can be different
+}
+
+pub const ALL_GENERATORS: [Generator; 6] = [Generator::Drunk, Generator::Cave, Generator::Bsp, Generator::Grammar, Generator::Noise, Generator::Mixed];
+
+impl Generator {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Generator::Drunk => "drunk",
+            Generator::Cave => "cave",
+            Generator::Bsp => "bsp",
+            Generator::Grammar => "grammar",
+            Generator::Noise => "noise",
+            Generator::Mixed => "mixed",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Generator> {
+        ALL_GENERATORS.iter().find(|g| g.name() == name).copied()
+    }
+
+    pub fn generate(&self, seed: u64, params: &LevelGenParams) -> SimulationState {
+        match self {
+            Generator::Drunk => generate_level_drunk(seed, params),
+            Generator::Cave => generate_level_cave(seed, params),
+            Generator::Bsp => generate_level_bsp(seed, params).0,
+            Generator::Grammar => generate_level_grammar(seed, params).0,
+            Generator::Noise => generate_level_noise(seed, params),
+            Generator::Mixed => {
+                let choices = [Generator::Drunk, Generator::Cave, Generator::Bsp, Generator::Grammar, Generator::Noise];
+                choices[(seed % choices.len() as u64) as usize].generate(seed, params)
+            },
+        }
+    }
+}
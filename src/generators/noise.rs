@@ -0,0 +1,202 @@
+use crate::grid::*;
+use crate::simulation_state::*;
+use crate::generators::params::*;
+
+use rand::Rng;
+use rand::SeedableRng;
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
+
+/*
+open arenas from fractal value noise: ground wherever the noise is under the threshold.
+leftover pockets get tunnelled into the main area so the player can reach everything
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseParams {
+    pub octaves: u32,
+    pub scale: f32,         // tiles per lattice cell of the first octave
+    pub persistence: f32,   // how much each octave counts compared to the last
+    pub threshold: f32,     // 0..1, higher is more open
+}
+
+impl Default for NoiseParams {
+    fn default() -> NoiseParams {
+        NoiseParams {
+            octaves: 3,
+            scale: 8.0,
+            persistence: 0.5,
+            threshold: 0.5,
+        }
+    }
+}
+
+// random value in 0..1 for a lattice point
+fn lattice(seed: u64, x: i32, y: i32, octave: u32) -> f32 {
+    let mut h = seed ^ (x as u32 as u64).wrapping_mul(0x9E3779B97F4A7C15) ^ (y as u32 as u64).wrapping_mul(0xC2B2AE3D27D4EB4F) ^ (octave as u64).wrapping_mul(0x165667B19E3779F9);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51AFD7ED558CCD);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xC4CEB9FE1A85EC53);
+    h ^= h >> 33;
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn value_noise(seed: u64, x: f32, y: f32, octave: u32) -> f32 {
+    let (x0, y0) = (x.floor() as i32, y.floor() as i32);
+    let (tx, ty) = (smoothstep(x - x0 as f32), smoothstep(y - y0 as f32));
+    let top = lattice(seed, x0, y0, octave) * (1.0 - tx) + lattice(seed, x0 + 1, y0, octave) * tx;
+    let bot = lattice(seed, x0, y0 + 1, octave) * (1.0 - tx) + lattice(seed, x0 + 1, y0 + 1, octave) * tx;
+    top * (1.0 - ty) + bot * ty
+}
+
+// sum of octaves normalized back into 0..1
+pub fn fractal_noise(seed: u64, x: f32, y: f32, params: &NoiseParams) -> f32 {
+    let mut total = 0.0;
+    let mut max = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0 / params.scale;
+    for octave in 0..params.octaves {
+        total += value_noise(seed, x * frequency, y * frequency, octave) * amplitude;
+        max += amplitude;
+        amplitude *= params.persistence;
+        frequency *= 2.0;
+    }
+    if max == 0.0 { 0.0 } else { total / max }
+}
+
+fn carve_line(g: &mut Grid, from: (i32, i32), to: (i32, i32)) {
+    let corner = (to.0, from.1);
+    for &(a, b) in [(from, corner), (corner, to)].iter() {
+        for x in a.0.min(b.0)..=a.0.max(b.0) {
+            for y in a.1.min(b.1)..=a.1.max(b.1) {
+                g.set_2d(x, y, Tile::Ground);
+            }
+        }
+    }
+}
+
+// tunnels every region into the biggest one, returns all the ground tiles
+pub fn connect_regions(g: &mut Grid) -> Vec<(i32, i32)> {
+    let mut regions: Vec<Vec<(i32, i32)>> = Vec::new();
+    let mut seen = vec![false; g.tiles.len()];
+    for y in 0..g.h {
+        for x in 0..g.w {
+            if seen[(x + y * g.w) as usize] || g.get_2d(x, y) != Some(Tile::Ground) { continue; }
+            let region = g.flood_fill(x, y);
+            for (rx, ry) in region.iter() {
+                seen[(rx + ry * g.w) as usize] = true;
+            }
+            regions.push(region);
+        }
+    }
+    if regions.is_empty() {
+        return Vec::new();
+    }
+
+    regions.sort_by_key(|r| std::cmp::Reverse(r.len()));
+    let mut connected = regions.remove(0);
+    for region in regions.iter() {
+        // closest pair, manhattan
+        let mut best = (region[0], connected[0]);
+        let dist = |a: (i32, i32), b: (i32, i32)| (a.0 - b.0).abs() + (a.1 - b.1).abs();
+        for a in region.iter() {
+            for b in connected.iter() {
+                if dist(*a, *b) < dist(best.0, best.1) {
+                    best = (*a, *b);
+                }
+            }
+        }
+        carve_line(g, best.0, best.1);
+        connected.extend(region.iter());
+    }
+
+    let start = connected[0];
+    g.flood_fill(start.0, start.1)
+}
+
+pub fn generate_level_noise(seed: u64, params: &LevelGenParams) -> SimulationState {
+    let noise = &params.noise;
+    let side_length = params.side_length;
+    let elem_size = params.elem_size;
+    let num_spawns = 40;
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let noise_seed: u64 = rng.gen();
+
+    let mut g = Grid::new(side_length, side_length, elem_size, elem_size);
+    for x in 1..side_length-1 {
+        for y in 1..side_length-1 {
+            if fractal_noise(noise_seed, x as f32, y as f32, noise) < noise.threshold {
+                g.set_2d(x, y, Tile::Ground);
+            }
+        }
+    }
+
+    let mut ground = connect_regions(&mut g);
+    if ground.is_empty() {
+        // threshold too low for anything to be open
+        let mid = (side_length/2, side_length/2);
+        g.set_2d(mid.0, mid.1, Tile::Ground);
+        ground.push(mid);
+    }
+
+    ground.sort();
+    ground.shuffle(&mut rng);
+    let player_tile = ground[0];
+    let spawn_tiles: Vec<(i32, i32)> = far_enough_from(&g, player_tile, &ground, params.min_spawn_distance)
+        .into_iter()
+        .take(num_spawns)
+        .collect();
+
+    populate_level(g, player_tile, &spawn_tiles, &[], params, rng, seed)
+}
+
+#[test]
+fn test_noise_range() {
+    let params = NoiseParams::default();
+    for i in 0..1000 {
+        let n = fractal_noise(5, i as f32 * 0.37, i as f32 * 0.61, &params);
+        assert!((0.0..=1.0).contains(&n));
+    }
+    assert_eq!(fractal_noise(5, 3.3, 4.4, &params), fractal_noise(5, 3.3, 4.4, &params));
+    assert_ne!(fractal_noise(5, 3.3, 4.4, &params), fractal_noise(6, 3.3, 4.4, &params));
+}
+
+#[test]
+fn test_noise_level() {
+    use crate::entity::*;
+
+    let open = |params: &LevelGenParams| {
+        let state = generate_level_noise(11, params);
+        state.terrain.tiles.iter().filter(|t| **t == Tile::Ground).count()
+    };
+    let with_threshold = |threshold| LevelGenParams {noise: NoiseParams {threshold: threshold, ..NoiseParams::default()}, ..LevelGenParams::default()};
+    let tight = with_threshold(0.35);
+    let wide = with_threshold(0.65);
+    assert!(open(&tight) < open(&wide));
+
+    for seed in 0..10 {
+        for params in [&tight, &LevelGenParams::default(), &wide].iter() {
+            let state = generate_level_noise(seed, params);
+            let g = &state.terrain;
+
+            // player can walk to every enemy
+            let player = state.entities.values().find(|e| e.variety == EntityType::Player).unwrap();
+            let (px, py) = g.get_xy_of_position(player.aabb.center());
+            let reachable = g.flood_fill(px, py);
+            for e in state.entities.values() {
+                assert!(reachable.contains(&g.get_xy_of_position(e.aabb.center())));
+            }
+        }
+    }
+
+    // size comes from the settings
+    let params = LevelGenParams {side_length: 30, elem_size: 0.15, ..LevelGenParams::default()};
+    let g = generate_level_noise(3, &params).terrain;
+    assert_eq!((g.w, g.h, g.elem_w, g.elem_h), (30, 30, 0.15, 0.15));
+}
@@ -0,0 +1,317 @@
+use crate::entity::*;
+use crate::level::*;
+use crate::snapshot::parse_entity_type;
+use crate::generators::noise::NoiseParams;
+use crate::systems::projectiles::*;
+use crate::textformat::*;
+
+use std::fs;
+use std::io;
+use rand::Rng;
+
+/*
+knobs for the level generators so they can be tuned without recompiling
+
+rustlandkings levelgen 1
+side_length 40
+elem_size 0.2
+num_walkers 40
+walk_iters 20
+p_change_dir 0.3
+noise_octaves 3
+noise_scale 8
+noise_persistence 0.5
+noise_threshold 0.5
+min_spawn_distance 0     walking distance in tiles from the player
+spawn Enemy 1       weight, chance is weight / total of all the weights
+spawn Crate 1
+...
+
+anything missing keeps its default, spawn lines replace the whole table
+*/
+
+const LEVELGEN_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelGenParams {
+    // drunk walk. the grammar generator uses side_length too, rounded down to whole rooms
+    pub side_length: i32,
+    pub elem_size: f32,
+    pub num_walkers: i32,
+    pub walk_iters: i32,
+    pub p_change_dir: f32,
+
+    pub noise: NoiseParams,
+
+    // nothing spawns closer than this to the player, in tiles of walking
+    pub min_spawn_distance: f32,
+
+    // what goes on each spawn tile, every generator uses this
+    pub spawn_table: Vec<(EntityType, u32)>,
+}
+
+impl Default for LevelGenParams {
+    fn default() -> LevelGenParams {
+        LevelGenParams {
+            side_length: 40,
+            elem_size: 0.2,
+            num_walkers: 40,
+            walk_iters: 20,
+            p_change_dir: 0.3,
+            noise: NoiseParams::default(),
+            min_spawn_distance: 0.0,
+            spawn_table: vec![
+                (EntityType::Enemy, 2),
+                (EntityType::Crate, 2),
+                (EntityType::Retaliator, 2),
+                (EntityType::Swarmer, 2),
+                (EntityType::Sprayer, 2),
+                (EntityType::Bigdog, 2),
+                // adding these changed the level of every seed, old leaderboard runs were on something else
+                (EntityType::GunPickup(GunKind::Pistol), 1),
+                (EntityType::GunPickup(GunKind::Makina), 1),
+                (EntityType::GunPickup(GunKind::BurstRifle), 1),
+                (EntityType::GunPickup(GunKind::Shotgun), 1),
+                (EntityType::GunPickup(GunKind::Scattergun), 1),
+            ],
+        }
+    }
+}
+
+// things fine to drop anywhere any number of times. not the player, or keys and doors which
+// only mean something where a generator puts them
+pub fn can_scatter(kind: EntityType) -> bool {
+    matches!(kind, EntityType::Crate | EntityType::Retaliator | EntityType::Enemy | EntityType::Swarmer |
+        EntityType::Sprayer | EntityType::Bigdog | EntityType::GunPickup(_))
+}
+
+// rows of the debug ui, in order
+pub const N_SCALAR_KNOBS: usize = 10;
+
+// per scalar knob: name in the file, label on screen, step, min, max. the range is whatever still generates
+const SCALAR_KNOBS: [(&str, &str, f32, f32, f32); N_SCALAR_KNOBS] = [
+    ("side_length", "side length", 1.0, 8.0, f32::INFINITY),
+    ("elem_size", "tile size", 0.01, 0.05, f32::INFINITY),
+    ("num_walkers", "walkers", 1.0, 2.0, f32::INFINITY),
+    ("walk_iters", "walk steps", 1.0, 2.0, f32::INFINITY),
+    ("p_change_dir", "turn chance", 0.05, 0.0, 1.0),
+    ("noise_octaves", "noise octaves", 1.0, 1.0, f32::INFINITY),
+    ("noise_scale", "noise scale", 0.5, 1.0, f32::INFINITY),
+    ("noise_persistence", "noise persistence", 0.05, 0.0, 1.0),
+    ("noise_threshold", "noise threshold", 0.05, 0.0, 1.0),
+    ("min_spawn_distance", "min spawn distance", 1.0, 0.0, f32::INFINITY),
+];
+
+impl LevelGenParams {
+    // None if every weight is 0
+    pub fn spawn(&self, rng: &mut impl Rng, x: f32, y: f32) -> Option<Entity> {
+        let total: u32 = self.spawn_table.iter().map(|(_, w)| w).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = rng.gen_range(0..total);
+        for (kind, weight) in self.spawn_table.iter() {
+            if roll < *weight {
+                return char_for_entity(*kind).and_then(|c| entity_for_char(c, x, y));
+            }
+            roll -= weight;
+        }
+        None
+    }
+
+    pub fn n_knobs(&self) -> usize {
+        N_SCALAR_KNOBS + self.spawn_table.len()
+    }
+
+    pub fn knob_value(&self, knob: usize) -> f32 {
+        match knob {
+            0 => self.side_length as f32,
+            1 => self.elem_size,
+            2 => self.num_walkers as f32,
+            3 => self.walk_iters as f32,
+            4 => self.p_change_dir,
+            5 => self.noise.octaves as f32,
+            6 => self.noise.scale,
+            7 => self.noise.persistence,
+            8 => self.noise.threshold,
+            9 => self.min_spawn_distance,
+            i => self.spawn_table.get(i - N_SCALAR_KNOBS).map(|(_, w)| *w as f32).unwrap_or(0.0),
+        }
+    }
+
+    pub fn knob_label(&self, knob: usize) -> String {
+        match SCALAR_KNOBS.get(knob) {
+            Some((_, label, _, _, _)) => label.to_string(),
+            None => self.spawn_table.get(knob - N_SCALAR_KNOBS).map(|(kind, _)| match kind {
+                EntityType::GunPickup(gun) => gun.name().replace('_', " "),
+                _ => format!("{:?}", kind).to_lowercase(),
+            }).unwrap_or_default(),
+        }
+    }
+
+    fn set_knob(&mut self, knob: usize, value: f32) {
+        match knob {
+            0 => self.side_length = value as i32,
+            1 => self.elem_size = value,
+            2 => self.num_walkers = value as i32,
+            3 => self.walk_iters = value as i32,
+            4 => self.p_change_dir = value,
+            5 => self.noise.octaves = value as u32,
+            6 => self.noise.scale = value,
+            7 => self.noise.persistence = value,
+            8 => self.noise.threshold = value,
+            9 => self.min_spawn_distance = value,
+            i => if let Some((_, w)) = self.spawn_table.get_mut(i - N_SCALAR_KNOBS) {
+                *w = value as u32;
+            },
+        }
+    }
+
+    // nudge a knob up or down by some steps, clamped to something that still generates
+    pub fn adjust_knob(&mut self, knob: usize, steps: i32) {
+        let (step, min, max) = match SCALAR_KNOBS.get(knob) {
+            Some((_, _, step, min, max)) => (*step, *min, *max),
+            None => (1.0, 0.0, f32::INFINITY),  // spawn weight
+        };
+        self.set_knob(knob, (self.knob_value(knob) + step * steps as f32).max(min).min(max));
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut out = header("levelgen", LEVELGEN_VERSION);
+        out += &format!("side_length {}\n", self.side_length);
+        out += &format!("elem_size {}\n", self.elem_size);
+        out += &format!("num_walkers {}\n", self.num_walkers);
+        out += &format!("walk_iters {}\n", self.walk_iters);
+        out += &format!("p_change_dir {}\n", self.p_change_dir);
+        out += &format!("noise_octaves {}\n", self.noise.octaves);
+        out += &format!("noise_scale {}\n", self.noise.scale);
+        out += &format!("noise_persistence {}\n", self.noise.persistence);
+        out += &format!("noise_threshold {}\n", self.noise.threshold);
+        out += &format!("min_spawn_distance {}\n", self.min_spawn_distance);
+        for (kind, weight) in self.spawn_table.iter() {
+            out += &format!("spawn {:?} {}\n", kind, weight);
+        }
+        out
+    }
+
+    pub fn deserialize(text: &str) -> io::Result<LevelGenParams> {
+        let mut lines = text.lines().enumerate();
+        check_header(lines.next().map(|(_, l)| l), "levelgen", &[LEVELGEN_VERSION])?;
+
+        let mut params = LevelGenParams::default();
+        let mut spawn_table = Vec::new();
+        for (i, line) in lines {
+            let t: Vec<&str> = line.split_whitespace().collect();
+            if t.is_empty() { continue; }
+            let bad = || bad_data(format!("line {}: bad {}", i + 1, t[0]));
+            let value = |n: usize| t.get(n).ok_or_else(bad);
+            match t[0] {
+                "side_length" => params.side_length = value(1)?.parse().map_err(|_| bad())?,
+                "elem_size" => params.elem_size = value(1)?.parse().map_err(|_| bad())?,
+                "num_walkers" => params.num_walkers = value(1)?.parse().map_err(|_| bad())?,
+                "walk_iters" => params.walk_iters = value(1)?.parse().map_err(|_| bad())?,
+                "p_change_dir" => params.p_change_dir = value(1)?.parse().map_err(|_| bad())?,
+                "noise_octaves" => params.noise.octaves = value(1)?.parse().map_err(|_| bad())?,
+                "noise_scale" => params.noise.scale = value(1)?.parse().map_err(|_| bad())?,
+                "noise_persistence" => params.noise.persistence = value(1)?.parse().map_err(|_| bad())?,
+                "noise_threshold" => params.noise.threshold = value(1)?.parse().map_err(|_| bad())?,
+                "min_spawn_distance" => params.min_spawn_distance = value(1)?.parse().map_err(|_| bad())?,
+                "spawn" => {
+                    let kind = parse_entity_type(value(1)?).filter(|k| can_scatter(*k)).ok_or_else(bad)?;
+                    spawn_table.push((kind, value(2)?.parse().map_err(|_| bad())?));
+                },
+                _ => return Err(bad_data(format!("line {}: unknown setting {}", i + 1, t[0]))),
+            }
+        }
+        if !spawn_table.is_empty() {
+            params.spawn_table = spawn_table;
+        }
+        // same limits as tuning in game
+        for (knob, (name, _, _, min, max)) in SCALAR_KNOBS.iter().enumerate() {
+            let value = params.knob_value(knob);
+            if !(*min..=*max).contains(&value) {
+                return Err(bad_data(format!("{} {} out of range, should be {} to {}", name, value, min, max)));
+            }
+        }
+        Ok(params)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.serialize())
+    }
+
+    // missing file is just the defaults
+    pub fn load(path: &str) -> io::Result<LevelGenParams> {
+        match fs::read_to_string(path) {
+            Ok(text) => LevelGenParams::deserialize(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(LevelGenParams::default()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[test]
+fn test_levelgen_params() {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    let mut params = LevelGenParams {p_change_dir: 0.45, ..LevelGenParams::default()};
+    params.adjust_knob(0, -3);
+    params.adjust_knob(N_SCALAR_KNOBS + 2, 4);
+    assert_eq!(params.side_length, 37);
+    assert_eq!(params.knob_value(N_SCALAR_KNOBS + 2), 6.0);
+    assert_eq!(LevelGenParams::deserialize(&params.serialize()).unwrap(), params);
+
+    let partial = LevelGenParams::deserialize("rustlandkings levelgen 1\nwalk_iters 50\nspawn Swarmer 3\n").unwrap();
+    assert_eq!(partial.walk_iters, 50);
+    assert_eq!(partial.num_walkers, LevelGenParams::default().num_walkers);
+    assert_eq!(partial.spawn_table, vec![(EntityType::Swarmer, 3)]);
+
+    for kind in ["Bullet", "Player", "Key", "Door", "ThrownGun"].iter() {
+        assert!(LevelGenParams::deserialize(&format!("rustlandkings levelgen 1\nspawn {} 3\n", kind)).is_err());
+    }
+    assert!(LevelGenParams::default().spawn_table.iter().all(|(k, _)| can_scatter(*k)));
+    assert!(LevelGenParams::deserialize("rustlandkings levelgen 1\nwobble 3\n").is_err());
+    assert!(LevelGenParams::deserialize("rustlandkings levelgen 2\n").is_err());
+
+    // out of range is an error, not clamped
+    for setting in ["side_length 5", "elem_size 0", "num_walkers 1", "walk_iters 0", "p_change_dir 1.5", "noise_octaves 0",
+                    "noise_scale 0.5", "noise_persistence -0.1", "noise_threshold 2", "min_spawn_distance -1", "p_change_dir NaN"].iter() {
+        assert!(LevelGenParams::deserialize(&format!("rustlandkings levelgen 1\n{}\n", setting)).is_err(), "{}", setting);
+    }
+    let mut clamped = LevelGenParams::default();
+    clamped.adjust_knob(0, -100);
+    clamped.adjust_knob(4, 100);
+    assert_eq!((clamped.side_length, clamped.p_change_dir), (8, 1.0));
+    assert_eq!(LevelGenParams::deserialize(&clamped.serialize()).unwrap(), clamped);
+    assert_eq!(clamped.knob_label(9), "min spawn distance");
+    assert_eq!(clamped.knob_label(N_SCALAR_KNOBS + 5), "bigdog");
+
+    // weights are respected, zero never comes up
+    let mut rng = ChaCha8Rng::seed_from_u64(1);
+    let weighted = LevelGenParams {spawn_table: vec![(EntityType::Crate, 0), (EntityType::Swarmer, 1)], ..LevelGenParams::default()};
+    for _ in 0..100 {
+        assert_eq!(weighted.spawn(&mut rng, 0.0, 0.0).unwrap().variety, EntityType::Swarmer);
+    }
+    let empty = LevelGenParams {spawn_table: vec![(EntityType::Crate, 0)], ..LevelGenParams::default()};
+    assert!(empty.spawn(&mut rng, 0.0, 0.0).is_none());
+}
+
+#[test]
+fn test_min_spawn_distance() {
+    use crate::simulation_state::*;
+    use crate::pathfinding::*;
+
+    let params = LevelGenParams {min_spawn_distance: 12.0, ..LevelGenParams::default()};
+    for seed in 0..5 {
+        let state = generate_level_drunk(seed, &params);
+        let g = &state.terrain;
+        let player = state.entities.values().find(|e| e.variety == EntityType::Player).unwrap();
+        let (px, py) = g.get_xy_of_position(player.aabb.center());
+        let field = FlowField::new(g, (px, py));
+        for e in state.entities.values().filter(|e| e.variety != EntityType::Player) {
+            let (x, y) = g.get_xy_of_position(e.aabb.center());
+            assert!(field.distance(x, y).unwrap() >= params.min_spawn_distance);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use crate::grid::*;
+use crate::entity::*;
+use crate::rect::*;
+use crate::vec2::*;
+use crate::simulation_state::*;
+
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+
+/*
+generators put things in the middle of a tile, but a big thing on a small tile pokes into the walls
+and the drunk walkers like to finish on the same tile, so things get stacked.
+this moves anything that doesnt fit: first a little within its tile, then out to the nearest tile with room
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Placement {
+    AsIs,
+    Nudged(Vec2),       // new center, same tile
+    Relocated(Vec2),    // new center, somewhere else
+    NoRoom,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpawnReport {
+    pub nudged: u32,
+    pub relocated: u32,
+    pub dropped: Vec<EntityType>,   // had nowhere to go so they were removed
+    pub too_close: Vec<EntityType>, // got moved within min spawn distance of the player so they were removed
+}
+
+impl SpawnReport {
+    pub fn is_ok(&self) -> bool {
+        self.dropped.is_empty() && self.too_close.is_empty()
+    }
+}
+
+// touching edges is fine
+fn overlaps(a: Rect, b: Rect) -> bool {
+    a.left() < b.right() && b.left() < a.right() && a.top() < b.bot() && b.top() < a.bot()
+}
+
+pub fn touches_wall(g: &Grid, aabb: Rect) -> bool {
+    let x0 = (aabb.left() / g.elem_w).floor() as i32;
+    let y0 = (aabb.top() / g.elem_h).floor() as i32;
+    let x1 = (aabb.right() / g.elem_w).ceil() as i32 - 1;
+    let y1 = (aabb.bot() / g.elem_h).ceil() as i32 - 1;
+    for x in x0..=x1 {
+        for y in y0..=y1 {
+            if g.get_2d(x, y) != Some(Tile::Ground) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+pub fn spot_is_clear(g: &Grid, placed: &BTreeMap<u32, Entity>, aabb: Rect) -> bool {
+    !touches_wall(g, aabb) && !placed.values().any(|e| overlaps(e.aabb, aabb))
+}
+
+// centers to try in a tile, middle first
+fn tile_spots(g: &Grid, x: i32, y: i32) -> Vec<Vec2> {
+    let center = g.get_rect_2d(x, y).center();
+    let mut offsets = Vec::new();
+    for i in -2..=2 {
+        for j in -2..=2 {
+            offsets.push((i, j));
+        }
+    }
+    offsets.sort_by_key(|(i, j)| i * i + j * j);
+    offsets.iter().map(|(i, j)| Vec2::new(center.x + *i as f32 * g.elem_w / 4.0, center.y + *j as f32 * g.elem_h / 4.0)).collect()
+}
+
+fn clear_spot_in_tile(g: &Grid, placed: &BTreeMap<u32, Entity>, aabb: Rect, x: i32, y: i32) -> Option<Vec2> {
+    tile_spots(g, x, y).into_iter()
+        .find(|c| spot_is_clear(g, placed, Rect::new_centered(c.x, c.y, aabb.w, aabb.h)))
+}
+
+pub fn find_spot(g: &Grid, placed: &BTreeMap<u32, Entity>, aabb: Rect) -> Placement {
+    if spot_is_clear(g, placed, aabb) {
+        return Placement::AsIs;
+    }
+    let (tx, ty) = g.get_xy_of_position(aabb.center());
+    if let Some(c) = clear_spot_in_tile(g, placed, aabb, tx, ty) {
+        return Placement::Nudged(c);
+    }
+
+    // nearest first through the ground its already on, so it stays reachable
+    let mut visited = vec![false; g.tiles.len()];
+    let mut queue = VecDeque::new();
+    queue.push_back((tx, ty));
+    while let Some((x, y)) = queue.pop_front() {
+        if g.get_2d(x, y) != Some(Tile::Ground) { continue; }
+        let i = (x + y * g.w) as usize;
+        if visited[i] { continue; }
+        visited[i] = true;
+
+        if (x, y) != (tx, ty) {
+            if let Some(c) = clear_spot_in_tile(g, placed, aabb, x, y) {
+                return Placement::Relocated(c);
+            }
+        }
+        queue.push_back((x + 1, y));
+        queue.push_back((x - 1, y));
+        queue.push_back((x, y + 1));
+        queue.push_back((x, y - 1));
+    }
+    Placement::NoRoom
+}
+
+// player first so it never gets pushed around, then doors so nothing gets moved into their doorway,
+// then everything else in id order
+pub fn fix_spawns(state: &mut SimulationState) -> SpawnReport {
+    let mut report = SpawnReport::default();
+    let rank = |e: &Entity| match e.variety {
+        EntityType::Player => 0,
+        EntityType::Door => 1,
+        _ => 2,
+    };
+    let mut order: Vec<u32> = state.entities.keys().copied().collect();
+    order.sort_by_key(|id| rank(&state.entities[id]));
+
+    let mut placed = BTreeMap::new();
+    for id in order {
+        let mut entity = state.entities[&id];
+        let center = match find_spot(&state.terrain, &placed, entity.aabb) {
+            Placement::AsIs => entity.aabb.center(),
+            Placement::Nudged(c) => { report.nudged += 1; c },
+            Placement::Relocated(c) => { report.relocated += 1; c },
+            Placement::NoRoom => {
+                report.dropped.push(entity.variety);
+                continue;
+            },
+        };
+        entity.aabb = Rect::new_centered(center.x, center.y, entity.aabb.w, entity.aabb.h);
+        placed.insert(id, entity);
+    }
+    state.entities = placed;
+    report
+}
+
+#[test]
+fn test_fix_spawns() {
+    use crate::level::*;
+
+    // stacked on one tile, and a retaliator that doesnt fit a small tile
+    let mut state = parse_level("#####\n#p..#\n#...#\n#####\n", 1).unwrap();
+    let center = state.terrain.get_rect_2d(2, 2).center();
+    for _ in 0..3 {
+        state.entities.insert(state.entities.len() as u32 + 1000, Entity::new_enemy(center.x, center.y));
+    }
+    let report = fix_spawns(&mut state);
+    assert!(report.is_ok());
+    assert_eq!(report.nudged + report.relocated, 2);
+    let rects: Vec<Rect> = state.entities.values().map(|e| e.aabb).collect();
+    for (i, a) in rects.iter().enumerate() {
+        assert!(!touches_wall(&state.terrain, *a));
+        for b in rects.iter().skip(i + 1) {
+            assert!(!overlaps(*a, *b));
+        }
+    }
+
+    // too much stuff for one tile, player stays put and the rest goes
+    let mut tiny = parse_level("###\n#p#\n###\n", 1).unwrap();
+    let player_before = tiny.entities.values().next().unwrap().aabb.center();
+    let center = tiny.terrain.get_rect_2d(1, 1).center();
+    tiny.entities.insert(0, Entity::new_retalliator(center.x, center.y));
+    let report = fix_spawns(&mut tiny);
+    assert_eq!(report.dropped, vec![EntityType::Retaliator]);
+    assert_eq!(tiny.entities.len(), 1);
+    assert_eq!(tiny.entities.values().next().unwrap().aabb.center(), player_before);
+}
+
+#[test]
+fn test_generated_levels_fit() {
+    use crate::generators::*;
+
+    for generator in ALL_GENERATORS.iter() {
+        for seed in 0..10 {
+            let state = generator.generate(seed, &LevelGenParams::default());
+            let rects: Vec<Rect> = state.entities.values().map(|e| e.aabb).collect();
+            for (i, a) in rects.iter().enumerate() {
+                assert!(!touches_wall(&state.terrain, *a), "{} seed {}", generator.name(), seed);
+                for b in rects.iter().skip(i + 1) {
+                    assert!(!overlaps(*a, *b), "{} seed {}", generator.name(), seed);
+                }
+            }
+        }
+    }
+}
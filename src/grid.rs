@@ -73,6 +73,27 @@ impl Grid {
         self.get_2d(ix, iy)
     }
 
+    // all the ground tiles 4-connected to (x, y), empty if its a wall
+    pub fn flood_fill(&self, x: i32, y: i32) -> Vec<(i32, i32)> {
+        let mut visited = vec![false; self.tiles.len()];
+        let mut region = Vec::new();
+        let mut stack = vec![(x, y)];
+
+        while let Some((x, y)) = stack.pop() {
+            if self.get_2d(x, y) != Some(Tile::Ground) { continue; }
+            let i = (x + y * self.w) as usize;
+            if visited[i] { continue; }
+            visited[i] = true;
+            region.push((x, y));
+
+            stack.push((x + 1, y));
+            stack.push((x - 1, y));
+            stack.push((x, y + 1));
+            stack.push((x, y - 1));
+        }
+        region
+    }
+
     // i still probably dont floor/ceil to the right size either
 
     // um infinte loop lol forgetting to increment anything?
@@ -273,4 +294,24 @@ fn test_raycast() {
 fn test_grid() {
     let g = Grid::new(10, 10, 1.0, 1.0);
     assert_eq!(g.get_xy_of_position(Vec2::new(5.5, 6.5)), (5, 6));
-}
\ No newline at end of file
+}
+#[test]
+fn test_flood_fill() {
+    /*
+    #####
+    #..##
+    ##.##
+    ##### <- (3, 3) is its own region
+    */
+    let mut g = Grid::new(5, 5, 1.0, 1.0);
+    g.set_2d(1, 1, Tile::Ground);
+    g.set_2d(2, 1, Tile::Ground);
+    g.set_2d(2, 2, Tile::Ground);
+    g.set_2d(3, 3, Tile::Ground);
+
+    let mut region = g.flood_fill(1, 1);
+    region.sort();
+    assert_eq!(region, vec![(1, 1), (2, 1), (2, 2)]);
+    assert_eq!(g.flood_fill(3, 3), vec![(3, 3)]);
+    assert_eq!(g.flood_fill(0, 0), vec![]);
+}
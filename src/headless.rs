@@ -0,0 +1,176 @@
+use crate::game::*;
+use crate::systems::command::*;
+use crate::replay::*;
+
+// runs the simulation with no sdl context, one slice of commands per frame
+pub fn run_headless(game: &mut Game, frames: &[Vec<Command>], dt: f64) {
+    for commands in frames.iter() {
+        game.clear_arenas();
+        game.push_commands(commands);
+        game.update(dt);
+    }
+}
+
+// idle frames, ai still runs
+pub fn run_headless_idle(game: &mut Game, n_frames: usize, dt: f64) {
+    for _ in 0..n_frames {
+        game.clear_arenas();
+        game.update(dt);
+    }
+}
+
+// plays a replay start to finish
+pub fn run_replay(game: &mut Game, replay: Replay) {
+    game.start_playback(replay);
+    while game.is_playing_back() {
+        game.clear_arenas();
+        game.update(0.0);   // dt comes from the replay
+    }
+}
+
+#[test]
+fn test_headless() {
+    use crate::screen_transform::*;
+    use crate::vec2::*;
+
+    let mut game = Game::new(ScreenTransform::new(1280, 720), 1234);
+    let player_id = game.player_id();
+    let start = game.state().entities.get(&player_id).unwrap().aabb.center();
+
+    let frames = vec![vec![Command::Walk(player_id, Vec2::new(1.0, 0.0))]; 10];
+    run_headless(&mut game, &frames, 1.0 / 60.0);
+    assert!((game.state().time - 10.0 / 60.0).abs() < 0.0001);
+
+    // either it walked or its dead or it hit a wall, but it mustnt go backwards
+    if let Some(player) = game.state().entities.get(&player_id) {
+        assert!(player.aabb.center().x >= start.x);
+    }
+
+    run_headless_idle(&mut game, 100, 1.0 / 60.0);
+    assert!((game.state().time - 110.0 / 60.0).abs() < 0.0001);
+}
+
+#[test]
+fn test_deterministic() {
+    use crate::screen_transform::*;
+    use crate::vec2::*;
+
+    let run = |seed| {
+        let mut game = Game::new(ScreenTransform::new(1280, 720), seed);
+        let player_id = game.player_id();
+        let frames: Vec<Vec<Command>> = (0..300).map(|i| vec![
+            Command::Walk(player_id, Vec2::new(1.0, 0.0).rotate(i as f32 * 0.05)),
+            Command::Look(player_id, Vec2::new(0.0, 1.0).rotate(i as f32 * 0.1)),
+            Command::Shoot(player_id),
+        ]).collect();
+        run_headless(&mut game, &frames, 1.0 / 60.0);
+        format!("{:?}", game.state().entities)
+    };
+
+    assert_eq!(run(1234), run(1234));
+    assert_ne!(run(1234), run(4321));
+}
+
+#[test]
+fn test_replay_playback() {
+    use crate::screen_transform::*;
+    use crate::vec2::*;
+
+    let mut game = Game::new(ScreenTransform::new(1280, 720), 99);
+    let player_id = game.player_id();
+    let frames: Vec<Vec<Command>> = (0..200).map(|i| vec![
+        Command::Walk(player_id, Vec2::new(0.0, 1.0).rotate(i as f32 * 0.03)),
+        Command::Shoot(player_id),
+    ]).collect();
+    run_headless(&mut game, &frames, 1.0 / 60.0);
+
+    // go through the file format too
+    let replay = Replay::deserialize(&game.recording().serialize()).unwrap();
+    let mut playback = Game::new(ScreenTransform::new(1280, 720), 1);
+    run_replay(&mut playback, replay);
+
+    assert_eq!(format!("{:?}", playback.state().entities), format!("{:?}", game.state().entities));
+    assert_eq!(playback.state().time, game.state().time);
+}
+
+#[test]
+fn test_replay_keeps_level_and_params() {
+    use crate::screen_transform::*;
+    use crate::vec2::*;
+    use crate::generators::*;
+    use crate::generators::params::*;
+
+    let record = |game: &mut Game| {
+        let player_id = game.player_id();
+        let frames: Vec<Vec<Command>> = (0..200).map(|i| vec![
+            Command::Walk(player_id, Vec2::new(1.0, 0.0).rotate(i as f32 * 0.04)),
+            Command::Shoot(player_id),
+        ]).collect();
+        run_headless(game, &frames, 1.0 / 60.0);
+
+        // a fresh game with other settings still plays it back
+        let replay = Replay::deserialize(&game.recording().serialize()).unwrap();
+        let mut playback = Game::new(ScreenTransform::new(1280, 720), 1);
+        run_replay(&mut playback, replay);
+        assert_eq!(format!("{:?}", playback.state().entities), format!("{:?}", game.state().entities));
+    };
+
+    let mut game = Game::new(ScreenTransform::new(1280, 720), 5);
+    game.set_gen_params(LevelGenParams {side_length: 30, walk_iters: 300, ..LevelGenParams::default()});
+    game.set_generator(Generator::Cave, 5);
+    record(&mut game);
+
+    let mut game = Game::new(ScreenTransform::new(1280, 720), 5);
+    game.set_level("#######\n#p....#\n#..e..#\n#######\n", 5).unwrap();
+    record(&mut game);
+}
+
+#[test]
+fn test_replay_after_load() {
+    use crate::screen_transform::*;
+    use crate::vec2::*;
+    use crate::simulation_state::*;
+
+    let mut game = Game::new(ScreenTransform::new(1280, 720), 77);
+    let player_id = game.player_id();
+    let frames: Vec<Vec<Command>> = (0..100).map(|i| vec![
+        Command::Walk(player_id, Vec2::new(0.0, 1.0).rotate(i as f32 * 0.05)),
+        Command::Shoot(player_id),
+    ]).collect();
+    run_headless(&mut game, &frames, 1.0 / 60.0);
+    let save = game.state().serialize();
+    run_headless(&mut game, &frames, 1.0 / 60.0);
+
+    // quickload partway through, the recording has to start from the save not the seed
+    game.load_state(SimulationState::deserialize(&save).unwrap());
+    run_headless(&mut game, &frames, 1.0 / 60.0);
+
+    let replay = Replay::deserialize(&game.recording().serialize()).unwrap();
+    assert_eq!(replay.frames.len(), 100);
+    let mut playback = Game::new(ScreenTransform::new(1280, 720), 1);
+    run_replay(&mut playback, replay);
+    assert_eq!(format!("{:?}", playback.state().entities), format!("{:?}", game.state().entities));
+    assert_eq!(playback.state().time, game.state().time);
+}
+
+#[test]
+fn test_run_goes_on_leaderboard() {
+    use crate::screen_transform::*;
+
+    // standing still gets you killed in plenty of levels, find one
+    let (seed, game) = (0..100).map(|seed| {
+        let mut game = Game::new(ScreenTransform::new(1280, 720), seed);
+        run_headless_idle(&mut game, 3000, 1.0 / 60.0);
+        (seed, game)
+    }).find(|(_, game)| !game.state().entities.contains_key(&game.player_id())).unwrap();
+
+    let runs = &game.leaderboard().runs;
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].seed, seed);
+    assert!(!runs[0].completed);
+    assert!(runs[0].damage_taken >= 5.0);
+
+    // lost to the seed
+    assert_eq!(game.ratings().player("player").games, 1);
+    assert!(game.ratings().seed(seed).rating > game.ratings().player("player").rating);
+}
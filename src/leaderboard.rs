@@ -0,0 +1,146 @@
+use crate::textformat::*;
+
+use std::fs;
+use std::io;
+
+/*
+seederboard: every finished run is kept, keyed by the level seed
+
+rustlandkings leaderboard 1
+run <seed> <completed 0/1> <time> <kills> <damage taken>
+*/
+
+const LEADERBOARD_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RunResult {
+    pub seed: u64,
+    pub completed: bool,
+    pub time: f64,
+    pub kills: u32,
+    pub damage_taken: f32,
+}
+
+impl RunResult {
+    // completing beats dying, then faster completions / longer survivals
+    pub fn is_better_than(&self, other: &RunResult) -> bool {
+        if self.completed != other.completed {
+            return self.completed;
+        }
+        if self.completed {
+            self.time < other.time
+        } else {
+            self.time > other.time
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Leaderboard {
+    pub runs: Vec<RunResult>,
+}
+
+impl Leaderboard {
+    pub fn new() -> Leaderboard {
+        Leaderboard { runs: Vec::new() }
+    }
+
+    pub fn add_run(&mut self, run: RunResult) {
+        self.runs.push(run);
+    }
+
+    pub fn best_for_seed(&self, seed: u64) -> Option<RunResult> {
+        self.runs.iter()
+            .filter(|r| r.seed == seed)
+            .fold(None, |best: Option<RunResult>, r| match best {
+                Some(b) if !r.is_better_than(&b) => Some(b),
+                _ => Some(*r),
+            })
+    }
+
+    // one entry per seed, best first
+    pub fn best_per_seed(&self) -> Vec<RunResult> {
+        let mut seeds: Vec<u64> = self.runs.iter().map(|r| r.seed).collect();
+        seeds.sort();
+        seeds.dedup();
+
+        let mut best: Vec<RunResult> = seeds.iter().filter_map(|s| self.best_for_seed(*s)).collect();
+        best.sort_by(|a, b| {
+            if a.is_better_than(b) {
+                std::cmp::Ordering::Less
+            } else if b.is_better_than(a) {
+                std::cmp::Ordering::Greater
+            } else {
+                a.seed.cmp(&b.seed)
+            }
+        });
+        best
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut out = header("leaderboard", LEADERBOARD_VERSION);
+        for r in self.runs.iter() {
+            out += &format!("run {} {} {} {} {}\n", r.seed, r.completed as u8, r.time, r.kills, r.damage_taken);
+        }
+        out
+    }
+
+    pub fn deserialize(text: &str) -> io::Result<Leaderboard> {
+        let mut lines = text.lines().enumerate();
+        check_header(lines.next().map(|(_, l)| l), "leaderboard", &[LEADERBOARD_VERSION])?;
+
+        let mut leaderboard = Leaderboard::new();
+        for (i, line) in lines {
+            let t: Vec<&str> = line.split_whitespace().collect();
+            if t.is_empty() { continue; }
+            let bad = || bad_data(format!("line {}: bad run", i + 1));
+            if t.len() != 6 || t[0] != "run" {
+                return Err(bad());
+            }
+            leaderboard.runs.push(RunResult {
+                seed: t[1].parse().map_err(|_| bad())?,
+                completed: parse_flag(t[2]).ok_or_else(bad)?,
+                time: t[3].parse().map_err(|_| bad())?,
+                kills: t[4].parse().map_err(|_| bad())?,
+                damage_taken: t[5].parse().map_err(|_| bad())?,
+            });
+        }
+        Ok(leaderboard)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.serialize())
+    }
+
+    // missing file is just an empty leaderboard
+    pub fn load(path: &str) -> io::Result<Leaderboard> {
+        match fs::read_to_string(path) {
+            Ok(text) => Leaderboard::deserialize(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Leaderboard::new()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[test]
+fn test_leaderboard() {
+    let run = |seed, completed, time| RunResult {seed: seed, completed: completed, time: time, kills: 3, damage_taken: 1.5};
+
+    let mut lb = Leaderboard::new();
+    lb.add_run(run(1, false, 30.0));
+    lb.add_run(run(1, true, 50.0));
+    lb.add_run(run(1, true, 40.0));
+    lb.add_run(run(2, false, 10.0));
+    lb.add_run(run(2, false, 20.0));
+    lb.add_run(run(3, true, 20.0));
+
+    assert_eq!(lb.best_for_seed(1), Some(run(1, true, 40.0)));
+    assert_eq!(lb.best_for_seed(2), Some(run(2, false, 20.0)));
+    assert_eq!(lb.best_for_seed(4), None);
+    assert_eq!(lb.best_per_seed(), vec![run(3, true, 20.0), run(1, true, 40.0), run(2, false, 20.0)]);
+
+    assert_eq!(Leaderboard::deserialize(&lb.serialize()).unwrap(), lb);
+    assert!(Leaderboard::deserialize("rustlandkings leaderboard 1\nrun 1 2 3\n").is_err());
+    // a typo in completed isnt a failed run
+    assert!(Leaderboard::deserialize("rustlandkings leaderboard 1\nrun 1 yes 30 3 1.5\n").is_err());
+}
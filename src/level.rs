@@ -0,0 +1,177 @@
+use crate::grid::*;
+use crate::entity::*;
+use crate::simulation_state::*;
+use crate::systems::projectiles::*;
+use crate::generators::placement::SpawnReport;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+/*
+authored levels, drawn like the grid diagrams in the tests
+
+; comments start with a semicolon
+##########
+#p...c...#
+#..r...s.#
+##########
+
+# wall, . ground, everything else is an entity standing on ground:
+p player, c crate, r retaliator, e enemy, s swarmer, y sprayer, b bigdog, k key, d door (opened by the key),
+1-5 gun pickups: pistol, makina, burst rifle, shotgun, scattergun
+short rows are padded with wall
+*/
+
+pub const LEVEL_TILE_SIZE: f32 = 0.2;
+
+pub fn entity_for_char(c: char, x: f32, y: f32) -> Option<Entity> {
+    match c {
+        'p' => Some(Entity::new_player(x, y)),
+        'c' => Some(Entity::new_crate(x, y)),
+        'r' => Some(Entity::new_retalliator(x, y)),
+        'e' => Some(Entity::new_enemy(x, y)),
+        's' => Some(Entity::new_swarmer(x, y)),
+        'y' => Some(Entity::new_sprayer(x, y)),
+        'b' => Some(Entity::new_bigdog(x, y)),
+        'k' => Some(Entity::new_key(x, y)),
+        'd' => Some(Entity::new_door(x, y, LEVEL_TILE_SIZE)),
+        '1'..='5' => Some(Entity::new_gun_pickup(x, y, ALL_GUN_KINDS[c as usize - '1' as usize])),
+        _ => None,
+    }
+}
+
+pub fn char_for_entity(variety: EntityType) -> Option<char> {
+    match variety {
+        EntityType::Player => Some('p'),
+        EntityType::Crate => Some('c'),
+        EntityType::Retaliator => Some('r'),
+        EntityType::Enemy => Some('e'),
+        EntityType::Swarmer => Some('s'),
+        EntityType::Sprayer => Some('y'),
+        EntityType::Bigdog => Some('b'),
+        EntityType::Bullet => None,
+        EntityType::ThrownGun => None,
+        EntityType::Key => Some('k'),
+        EntityType::Door => Some('d'),
+        EntityType::GunPickup(kind) => ALL_GUN_KINDS.iter().position(|k| *k == kind).map(|i| (b'1' + i as u8) as char),
+    }
+}
+
+// seed is just for the rng, the layout is all in the text
+pub fn parse_level(text: &str, seed: u64) -> io::Result<SimulationState> {
+    let rows: Vec<&str> = text.lines()
+        .map(|l| l.trim_end())
+        .filter(|l| !l.is_empty() && !l.starts_with(';'))
+        .collect();
+    let h = rows.len() as i32;
+    let w = rows.iter().map(|r| r.chars().count()).max().unwrap_or(0) as i32;
+    if w == 0 || h == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "empty level"));
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut g = Grid::new(w, h, LEVEL_TILE_SIZE, LEVEL_TILE_SIZE);
+    let mut entities = BTreeMap::new();
+    let mut n_players = 0;
+
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            let (x, y) = (x as i32, y as i32);
+            if c == '#' { continue; }
+            g.set_2d(x, y, Tile::Ground);
+            if c == '.' { continue; }
+
+            let pos = g.get_rect_2d(x, y).center();
+            match entity_for_char(c, pos.x, pos.y) {
+                Some(entity) => {
+                    if entity.variety == EntityType::Player {
+                        n_players += 1;
+                    }
+                    entities.insert(new_entity_id(&entities, &mut rng), entity);
+                },
+                None => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown tile '{}' at ({}, {})", c, x, y)));
+                },
+            }
+        }
+    }
+
+    if n_players != 1 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("level needs exactly one player, has {}", n_players)));
+    }
+
+    Ok(SimulationState {
+        time: 0.0,
+        dt: 0.016,
+        terrain: g,
+        entities: entities,
+        inventories: BTreeMap::new(),
+        spawn_report: SpawnReport::default(),
+        seed: seed,
+        rng: rng,
+    })
+}
+
+pub fn load_level(path: &str, seed: u64) -> io::Result<SimulationState> {
+    parse_level(&fs::read_to_string(path)?, seed)
+}
+
+// inverse of parse_level, entities snap to the tile their center is in
+pub fn level_to_string(state: &SimulationState) -> String {
+    let t = &state.terrain;
+    let mut chars: Vec<char> = t.tiles.iter().map(|tile| match tile {
+        Tile::Wall => '#',
+        Tile::Ground => '.',
+    }).collect();
+
+    for entity in state.entities.values() {
+        if let Some(c) = char_for_entity(entity.variety) {
+            let (x, y) = t.get_xy_of_position(entity.aabb.center());
+            if t.get_2d(x, y).is_some() {
+                chars[(x + y * t.w) as usize] = c;
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for row in chars.chunks(t.w as usize) {
+        out.extend(row.iter());
+        out.push('\n');
+    }
+    out
+}
+
+pub fn save_level(state: &SimulationState, path: &str) -> io::Result<()> {
+    fs::write(path, level_to_string(state))
+}
+
+#[test]
+fn test_parse_level() {
+    let state = parse_level("; test level\n#####\n#p.c#\n#..s\n#####\n", 1).unwrap();
+    assert_eq!((state.terrain.w, state.terrain.h), (5, 4));
+    assert_eq!(state.terrain.get_2d(1, 1), Some(Tile::Ground));
+    assert_eq!(state.terrain.get_2d(0, 1), Some(Tile::Wall));
+    assert_eq!(state.terrain.get_2d(4, 2), Some(Tile::Wall));    // padded
+    assert_eq!(state.terrain.get_2d(3, 2), Some(Tile::Ground));   // under the swarmer
+
+    let mut varieties: Vec<EntityType> = state.entities.values().map(|e| e.variety).collect();
+    varieties.sort();
+    assert_eq!(varieties, vec![EntityType::Player, EntityType::Crate, EntityType::Swarmer]);
+
+    let player = state.entities.values().find(|e| e.variety == EntityType::Player).unwrap();
+    assert_eq!(state.terrain.get_xy_of_position(player.aabb.center()), (1, 1));
+
+    assert_eq!(level_to_string(&state), "#####\n#p.c#\n#..s#\n#####\n");
+
+    let guns = parse_level("#####\n#p34#\n#####\n", 1).unwrap();
+    assert!(guns.entities.values().any(|e| e.variety == EntityType::GunPickup(GunKind::BurstRifle)));
+    assert!(guns.entities.values().any(|e| e.variety == EntityType::GunPickup(GunKind::Shotgun)));
+    assert_eq!(level_to_string(&guns), "#####\n#p34#\n#####\n");
+
+    assert!(parse_level("###\n#.#\n###\n", 1).is_err());
+    assert!(parse_level("####\n#pp#\n####\n", 1).is_err());
+    assert!(parse_level("####\n#px#\n####\n", 1).is_err());
+}
@@ -0,0 +1,23 @@
+pub mod game;
+pub mod rect;
+pub mod systems;
+pub mod generators;
+pub mod entity;
+pub mod screen_transform;
+pub mod grid;
+pub mod pathfinding;
+pub mod vec2;
+pub mod side_effect;
+pub mod simulation_state;
+pub mod headless;
+pub mod replay;
+pub mod leaderboard;
+pub mod rating;
+pub mod snapshot;
+pub mod level;
+pub mod editor;
+pub mod analysis;
+pub mod textformat;
+#[cfg(feature = "sdl")]
+pub mod font;
+pub mod color;
@@ -1,23 +1,88 @@
-mod game;
-mod rect;
-mod systems;
-mod entity;
-mod screen_transform;
-mod grid;
-mod vec2;
-mod side_effect;
-mod simulation_state;
-
-use crate::game::*;
-use screen_transform::ScreenTransform;
+use rustlandkings::game::*;
+use rustlandkings::headless;
+use rustlandkings::replay::*;
+use rustlandkings::generators::*;
+use rustlandkings::generators::params::*;
+use rustlandkings::analysis::*;
+use rustlandkings::screen_transform::ScreenTransform;
 use sdl2::pixels::Color;
-use sdl2::keyboard::Keycode;
-use sdl2::event::Event;
 use std::time::{Duration, SystemTime};
+use rand::Rng;
 
 fn main() {
     let xres = 1280;
     let yres = 720;
+
+    let args: Vec<String> = std::env::args().collect();
+
+    // --seed <n>: play a specific level
+    let seed = match args.iter().position(|a| a == "--seed") {
+        Some(i) => args.get(i+1).and_then(|n| n.parse().ok()).expect("--seed needs a number"),
+        None => rand::thread_rng().gen(),
+    };
+
+    // --replay <file>: play back a recorded run instead
+    let replay = args.iter().position(|a| a == "--replay").map(|i| {
+        let path = args.get(i+1).expect("--replay needs a file");
+        Replay::load(path).expect("couldnt load replay")
+    });
+
+    // --level <file>: play an authored level instead of a generated one
+    let level = args.iter().position(|a| a == "--level").map(|i| {
+        let path = args.get(i+1).expect("--level needs a file");
+        std::fs::read_to_string(path).expect("couldnt read level")
+    });
+    // --generator <drunk|cave|mixed>: which level generator
+    let generator = match args.iter().position(|a| a == "--generator") {
+        Some(i) => args.get(i+1).and_then(|n| Generator::from_name(n)).expect("--generator needs drunk, cave, bsp, grammar, noise or mixed"),
+        None => Generator::Drunk,
+    };
+    // --levelgen <file>: level generator settings, levelgen.txt by default. f4 tunes them in game
+    let levelgen_path = match args.iter().position(|a| a == "--levelgen") {
+        Some(i) => args.get(i+1).expect("--levelgen needs a file").clone(),
+        None => "levelgen.txt".to_string(),
+    };
+
+    // --analyze <n>: csv of level stats for seeds 0..n, for every generator unless --generator is given
+    if let Some(i) = args.iter().position(|a| a == "--analyze") {
+        let n_seeds: u64 = args.get(i+1).and_then(|n| n.parse().ok()).expect("--analyze needs a number of seeds");
+        let params = LevelGenParams::load(&levelgen_path).expect("couldnt load levelgen settings");
+        let generators = if args.iter().any(|a| a == "--generator") { vec![generator] } else { ALL_GENERATORS.to_vec() };
+        println!("generator,seed,{}", LevelReport::csv_header());
+        for generator in generators {
+            for seed in 0..n_seeds {
+                let report = analyze_level(&generator.generate(seed, &params));
+                println!("{},{},{}", generator.name(), seed, report.csv_row());
+            }
+        }
+        return;
+    }
+    println!("seed {}", seed);
+
+    let new_game = || {
+        let mut game = Game::new(ScreenTransform::new(xres, yres), seed);
+        game.load_gen_params(&levelgen_path).expect("couldnt load levelgen settings");
+        if generator != Generator::Drunk {
+            game.set_generator(generator, seed);
+        }
+        if let Some(level) = &level {
+            game.set_level(level, seed).expect("bad level");
+        }
+        game
+    };
+
+    // --headless <frames>: just simulate, no window
+    if let Some(i) = args.iter().position(|a| a == "--headless") {
+        let n_frames = args.get(i+1).and_then(|n| n.parse().ok()).unwrap_or(1000);
+        let mut game = new_game();
+        match replay {
+            Some(replay) => headless::run_replay(&mut game, replay),
+            None => headless::run_headless_idle(&mut game, n_frames, 1.0 / 60.0),
+        }
+        println!("simulated {:.2}s, {} entities", game.state().time, game.state().entities.len());
+        return;
+    }
+
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     let window = video_subsystem.window("rustland kings", xres, yres)
@@ -30,7 +95,20 @@ fn main() {
 
     let mut event_pump = sdl_context.event_pump().unwrap();
 
-    let mut game = Game::new(ScreenTransform::new(xres, yres));
+    let mut game = new_game();
+    if let Some(replay) = replay {
+        game.start_playback(replay);
+    }
+    if let Err(e) = game.load_leaderboard("leaderboard.txt") {
+        println!("couldnt load leaderboard: {}", e);
+    }
+    if let Err(e) = game.load_ratings("ratings.txt") {
+        println!("couldnt load ratings: {}", e);
+    }
+    // --profile <name>: whose rating finished runs count towards
+    if let Some(i) = args.iter().position(|a| a == "--profile") {
+        game.set_profile(args.get(i+1).expect("--profile needs a name"));
+    }
     let mut dt = 1.0f64 / 60f64;
 
     'running: loop {
@@ -38,6 +116,8 @@ fn main() {
 
         game.clear_arenas();
         if !game.handle_input(&mut event_pump) {
+            game.recording().save("last.replay").expect("couldnt save replay");
+            println!("saved last.replay");
             break 'running;
         }
         
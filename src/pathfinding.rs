@@ -0,0 +1,291 @@
+use crate::grid::*;
+use crate::rect::*;
+use crate::vec2::*;
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/*
+a* over the terrain grid, 8 directions, no cutting corners.
+
+big things need a bigger gap: anything over half a tile wide gets a 2x2 block of ground to stand on,
+otherwise it snags on every corner of a 1 tile corridor. so nodes are the top left tile of a
+k x k block of ground and the waypoints are the middles of those blocks
+*/
+
+const ORTHOGONAL_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+// side of the block of tiles something this size needs
+pub fn tiles_needed(g: &Grid, w: f32, h: f32) -> i32 {
+    ((w / g.elem_w).max(h / g.elem_h) * 2.0).ceil().max(1.0) as i32
+}
+
+// for each tile the biggest square of ground with it as the top left corner
+pub fn clearance_map(g: &Grid) -> Vec<i32> {
+    let mut clearance = vec![0; g.tiles.len()];
+    let at = |c: &Vec<i32>, x: i32, y: i32| if x < g.w && y < g.h { c[(x + y * g.w) as usize] } else { 0 };
+    for y in (0..g.h).rev() {
+        for x in (0..g.w).rev() {
+            if g.get_2d(x, y) != Some(Tile::Ground) { continue; }
+            let c = 1 + at(&clearance, x + 1, y).min(at(&clearance, x, y + 1)).min(at(&clearance, x + 1, y + 1));
+            clearance[(x + y * g.w) as usize] = c;
+        }
+    }
+    clearance
+}
+
+// only needs redoing when the terrain changes, the grid is passed back in for each search
+pub struct Pathfinder {
+    w: i32,
+    h: i32,
+    clearance: Vec<i32>,
+}
+
+impl Pathfinder {
+    pub fn new(grid: &Grid) -> Pathfinder {
+        Pathfinder {
+            w: grid.w,
+            h: grid.h,
+            clearance: clearance_map(grid),
+        }
+    }
+
+    fn walkable(&self, x: i32, y: i32, k: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.w && y < self.h && self.clearance[(x + y * self.w) as usize] >= k
+    }
+
+    // top left tile of the k block centered nearest to pos
+    fn node_of(g: &Grid, pos: Vec2, k: i32) -> (i32, i32) {
+        let half = k as f32 / 2.0;
+        ((pos.x / g.elem_w - half + 0.5).floor() as i32, (pos.y / g.elem_h - half + 0.5).floor() as i32)
+    }
+
+    fn node_center(g: &Grid, node: (i32, i32), k: i32) -> Vec2 {
+        let half = k as f32 / 2.0;
+        let r = g.get_rect_2d(node.0, node.1);
+        Vec2::new(r.x + half * g.elem_w, r.y + half * g.elem_h)
+    }
+
+    // waypoints for something with this aabb to get to target, not including where it is now.
+    // None if theres no way through for something that size. g has to be the grid this was made from
+    pub fn find_path(&self, g: &Grid, aabb: Rect, target: Vec2) -> Option<Vec<Vec2>> {
+        let k = tiles_needed(g, aabb.w, aabb.h);
+        let start = Pathfinder::node_of(g, aabb.center(), k);
+        let goal = Pathfinder::node_of(g, target, k);
+        if !self.walkable(goal.0, goal.1, k) || start.0 < 0 || start.1 < 0 || start.0 >= g.w || start.1 >= g.h {
+            return None;
+        }
+
+        let index = |(x, y): (i32, i32)| (x + y * g.w) as usize;
+        let heuristic = |(x, y): (i32, i32)| {
+            let (dx, dy) = ((x - goal.0).unsigned_abs(), (y - goal.1).unsigned_abs());
+            ORTHOGONAL_COST * dx.max(dy) + (DIAGONAL_COST - ORTHOGONAL_COST) * dx.min(dy)
+        };
+
+        let mut cost = vec![u32::MAX; g.tiles.len()];
+        let mut came_from = vec![usize::MAX; g.tiles.len()];
+        let mut open = BinaryHeap::new();
+        cost[index(start)] = 0;
+        open.push(Reverse((heuristic(start), 0, start)));
+
+        while let Some(Reverse((_, so_far, node))) = open.pop() {
+            if node == goal {
+                let mut path = Vec::new();
+                let mut i = index(goal);
+                while i != index(start) {
+                    path.push(Pathfinder::node_center(g, ((i as i32) % g.w, (i as i32) / g.w), k));
+                    i = came_from[i];
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if so_far > cost[index(node)] { continue; }
+
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    if dx == 0 && dy == 0 { continue; }
+                    let next = (node.0 + dx, node.1 + dy);
+                    if !self.walkable(next.0, next.1, k) { continue; }
+                    let diagonal = dx != 0 && dy != 0;
+                    if diagonal && !(self.walkable(node.0 + dx, node.1, k) && self.walkable(node.0, node.1 + dy, k)) { continue; }
+
+                    let next_cost = so_far + if diagonal { DIAGONAL_COST } else { ORTHOGONAL_COST };
+                    if next_cost < cost[index(next)] {
+                        cost[index(next)] = next_cost;
+                        came_from[index(next)] = index(node);
+                        open.push(Reverse((next_cost + heuristic(next), next_cost, next)));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/*
+flow field: dijkstra out from one tile (the player) over every tile, same moves and costs as the a*.
+everything chasing the same thing just walks downhill, so a swarm costs one search instead of one each
+*/
+
+pub struct FlowField {
+    pub origin: (i32, i32),
+    w: i32,
+    h: i32,
+    cost: Vec<u32>,     // u32::MAX if theres no way there
+}
+
+impl FlowField {
+    pub fn new(g: &Grid, origin: (i32, i32)) -> FlowField {
+        let mut field = FlowField {
+            origin: origin,
+            w: g.w,
+            h: g.h,
+            cost: vec![u32::MAX; g.tiles.len()],
+        };
+        if g.get_2d(origin.0, origin.1) != Some(Tile::Ground) {
+            return field;
+        }
+
+        let ground = |x: i32, y: i32| g.get_2d(x, y) == Some(Tile::Ground);
+        let index = |(x, y): (i32, i32)| (x + y * g.w) as usize;
+        let mut open = BinaryHeap::new();
+        field.cost[index(origin)] = 0;
+        open.push(Reverse((0, origin)));
+        while let Some(Reverse((so_far, node))) = open.pop() {
+            if so_far > field.cost[index(node)] { continue; }
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    if dx == 0 && dy == 0 { continue; }
+                    let next = (node.0 + dx, node.1 + dy);
+                    if !ground(next.0, next.1) { continue; }
+                    let diagonal = dx != 0 && dy != 0;
+                    if diagonal && !(ground(node.0 + dx, node.1) && ground(node.0, node.1 + dy)) { continue; }
+
+                    let next_cost = so_far + if diagonal { DIAGONAL_COST } else { ORTHOGONAL_COST };
+                    if next_cost < field.cost[index(next)] {
+                        field.cost[index(next)] = next_cost;
+                        open.push(Reverse((next_cost, next)));
+                    }
+                }
+            }
+        }
+        field
+    }
+
+    // walking distance from the origin in tiles
+    pub fn distance(&self, x: i32, y: i32) -> Option<f32> {
+        if x < 0 || y < 0 || x >= self.w || y >= self.h {
+            return None;
+        }
+        match self.cost[(x + y * self.w) as usize] {
+            u32::MAX => None,
+            c => Some(c as f32 / ORTHOGONAL_COST as f32),
+        }
+    }
+
+    // which way to walk from pos to get closer to the origin, None if its already there or cut off
+    pub fn direction(&self, g: &Grid, pos: Vec2) -> Option<Vec2> {
+        let (x, y) = g.get_xy_of_position(pos);
+        let here = self.distance(x, y)?;
+        let mut best: Option<((i32, i32), f32)> = None;
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                if dx == 0 && dy == 0 { continue; }
+                // no cutting corners here either
+                if dx != 0 && dy != 0 && (self.distance(x + dx, y).is_none() || self.distance(x, y + dy).is_none()) { continue; }
+                if let Some(d) = self.distance(x + dx, y + dy) {
+                    if d < here && best.map(|(_, b)| d < b).unwrap_or(true) {
+                        best = Some(((x + dx, y + dy), d));
+                    }
+                }
+            }
+        }
+        let ((bx, by), _) = best?;
+        Some(g.get_rect_2d(bx, by).center().sub(pos).normalize())
+    }
+}
+
+#[test]
+fn test_flow_field() {
+    use crate::level::*;
+
+    let state = parse_level("\
+#######
+#p....#
+####..#
+#.....#
+#######
+", 1).unwrap();
+    let g = &state.terrain;
+    let field = FlowField::new(g, (1, 1));
+    assert_eq!(field.distance(1, 1), Some(0.0));
+    assert_eq!(field.distance(3, 1), Some(2.0));
+    assert_eq!(field.distance(0, 0), None);
+    // round the end of the wall without cutting its corners
+    assert_eq!(field.distance(1, 3), Some(8.0));
+    assert_eq!(field.distance(5, 2), Some(4.4));
+
+    // downhill from under the wall is away from the player, towards the gap
+    let dir = field.direction(g, g.get_rect_2d(1, 3).center()).unwrap();
+    assert!(dir.x > 0.9);
+    assert!(field.direction(g, g.get_rect_2d(1, 1).center()).is_none());
+}
+
+#[test]
+fn test_find_path() {
+    use crate::level::*;
+
+    let state = parse_level("\
+#########
+#p..#...#
+#...#...#
+#...#...#
+#.......#
+#########
+", 1).unwrap();
+    let g = &state.terrain;
+    let pathfinder = Pathfinder::new(g);
+    let tile = |x: i32, y: i32| g.get_rect_2d(x, y).center();
+
+    // around the wall, ending on the target
+    let small = Rect::new_centered(tile(1, 1).x, tile(1, 1).y, 0.05, 0.05);
+    let path = pathfinder.find_path(g, small, tile(7, 1)).unwrap();
+    assert_eq!(*path.last().unwrap(), tile(7, 1));
+    for p in path.iter() {
+        assert_eq!(g.get_position(*p), Some(Tile::Ground));
+    }
+    // down, across, up, with diagonals: 3 + 6 + 3 steps less the corners that got cut
+    assert!(path.len() < 12);
+    assert!(path.iter().any(|p| g.get_xy_of_position(*p).1 == 4));
+
+    // already there
+    assert_eq!(pathfinder.find_path(g, small, tile(1, 1)), Some(Vec::new()));
+
+    // bigdog wont fit through the bottom corridor, but a 2 wide one is fine
+    let big = Rect::new_centered(tile(2, 2).x, tile(2, 2).y, 0.12, 0.12);
+    assert_eq!(tiles_needed(g, 0.12, 0.12), 2);
+    assert!(pathfinder.find_path(g, big, tile(6, 2)).is_none());
+
+    let wide = parse_level("\
+#########
+#p..#...#
+#...#...#
+#.......#
+#.......#
+#########
+", 1).unwrap();
+    let pathfinder = Pathfinder::new(&wide.terrain);
+    assert!(pathfinder.find_path(&wide.terrain, big, tile(6, 2)).is_some());
+}
+
+#[test]
+fn test_clearance_map() {
+    use crate::level::*;
+
+    let state = parse_level("####\n#p.#\n#..#\n####\n", 1).unwrap();
+    let c = clearance_map(&state.terrain);
+    assert_eq!(c[1 + 4], 2);
+    assert_eq!(c[2 + 4], 1);
+    assert_eq!(c[0], 0);
+}
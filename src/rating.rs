@@ -0,0 +1,149 @@
+use crate::textformat::*;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+/*
+elo where every run is a match between the player and the seed.
+completing the level is a win for the player, dying is a win for the seed.
+so seed ratings end up as a difficulty estimate
+
+rustlandkings ratings 1
+player <name> <rating> <games>
+seed <seed> <rating> <games>
+*/
+
+const RATINGS_VERSION: u32 = 1;
+pub const INITIAL_RATING: f64 = 1200.0;
+const K_FACTOR: f64 = 32.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub games: u32,
+}
+
+impl Rating {
+    pub fn new() -> Rating {
+        Rating { rating: INITIAL_RATING, games: 0 }
+    }
+}
+
+impl Default for Rating {
+    fn default() -> Rating {
+        Rating::new()
+    }
+}
+
+// chance a beats b
+pub fn expected_score(a: f64, b: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((b - a) / 400.0))
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Ratings {
+    pub players: BTreeMap<String, Rating>,
+    pub seeds: BTreeMap<u64, Rating>,
+}
+
+impl Ratings {
+    pub fn new() -> Ratings {
+        Ratings { players: BTreeMap::new(), seeds: BTreeMap::new() }
+    }
+
+    pub fn player(&self, name: &str) -> Rating {
+        self.players.get(name).copied().unwrap_or_else(Rating::new)
+    }
+
+    pub fn seed(&self, seed: u64) -> Rating {
+        self.seeds.get(&seed).copied().unwrap_or_else(Rating::new)
+    }
+
+    pub fn record_match(&mut self, player: &str, seed: u64, player_won: bool) {
+        let p = self.player(player);
+        let s = self.seed(seed);
+
+        let expected = expected_score(p.rating, s.rating);
+        let score = if player_won { 1.0 } else { 0.0 };
+        let delta = K_FACTOR * (score - expected);
+
+        self.players.insert(player.to_string(), Rating { rating: p.rating + delta, games: p.games + 1 });
+        self.seeds.insert(seed, Rating { rating: s.rating - delta, games: s.games + 1 });
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut out = header("ratings", RATINGS_VERSION);
+        for (name, r) in self.players.iter() {
+            out += &format!("player {} {} {}\n", name, r.rating, r.games);
+        }
+        for (seed, r) in self.seeds.iter() {
+            out += &format!("seed {} {} {}\n", seed, r.rating, r.games);
+        }
+        out
+    }
+
+    pub fn deserialize(text: &str) -> io::Result<Ratings> {
+        let mut lines = text.lines().enumerate();
+        check_header(lines.next().map(|(_, l)| l), "ratings", &[RATINGS_VERSION])?;
+
+        let mut ratings = Ratings::new();
+        for (i, line) in lines {
+            let t: Vec<&str> = line.split_whitespace().collect();
+            if t.is_empty() { continue; }
+            let bad = || bad_data(format!("line {}: bad rating", i + 1));
+            if t.len() != 4 {
+                return Err(bad());
+            }
+            let r = Rating {
+                rating: t[2].parse().map_err(|_| bad())?,
+                games: t[3].parse().map_err(|_| bad())?,
+            };
+            match t[0] {
+                "player" => { ratings.players.insert(t[1].to_string(), r); },
+                "seed" => { ratings.seeds.insert(t[1].parse().map_err(|_| bad())?, r); },
+                _ => return Err(bad()),
+            }
+        }
+        Ok(ratings)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.serialize())
+    }
+
+    // missing file is just no ratings yet
+    pub fn load(path: &str) -> io::Result<Ratings> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ratings::deserialize(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Ratings::new()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[test]
+fn test_ratings() {
+    assert_eq!(expected_score(1200.0, 1200.0), 0.5);
+    assert!(expected_score(1600.0, 1200.0) > 0.9);
+
+    let mut ratings = Ratings::new();
+    ratings.record_match("bob", 1, true);
+    assert_eq!(ratings.player("bob"), Rating { rating: 1216.0, games: 1 });
+    assert_eq!(ratings.seed(1), Rating { rating: 1184.0, games: 1 });
+    assert_eq!(ratings.seed(2), Rating::new());
+
+    // a seed that keeps killing people gets harder
+    for _ in 0..10 {
+        ratings.record_match("alice", 2, false);
+    }
+    assert!(ratings.seed(2).rating > ratings.seed(1).rating);
+    assert!(ratings.player("alice").rating < INITIAL_RATING);
+
+    // its zero sum
+    let total: f64 = ratings.players.values().chain(ratings.seeds.values()).map(|r| r.rating - INITIAL_RATING).sum();
+    assert!(total.abs() < 0.0001);
+
+    assert_eq!(Ratings::deserialize(&ratings.serialize()).unwrap(), ratings);
+    assert!(Ratings::deserialize("rustlandkings ratings 1\nplayer bob\n").is_err());
+}
@@ -0,0 +1,214 @@
+use crate::systems::command::*;
+use crate::vec2::*;
+use crate::generators::*;
+use crate::generators::params::*;
+use crate::level::*;
+use crate::simulation_state::*;
+use crate::textformat::*;
+
+use std::fs;
+use std::io;
+
+/*
+text format, one command per line:
+
+rustlandkings replay 2
+seed 1234 drunk     generator is optional, drunk if its missing
+levelgen 12         optional, the next 12 lines are the levelgen settings when they arent the defaults
+...
+level 20            optional, the next 20 lines are an authored level played instead of a generated one
+...
+snapshot 1700       optional, the next 1700 lines are the state to start from instead, eg. after a quickload or editing
+...
+frame 0.016666668 2
+walk 123 1 0
+shoot 123
+frame ...
+eat 123
+throw 123
+
+floats are written with {} which round trips exactly, so playback is bit identical.
+version 1 is the same without the levelgen, level and snapshot blocks
+*/
+
+const REPLAY_VERSION: u32 = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayFrame {
+    pub dt: f64,
+    pub commands: Vec<Command>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub generator: Generator,
+    pub gen_params: LevelGenParams,
+    pub level: Option<String>,
+    pub snapshot: Option<String>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+fn serialize_block(name: &str, text: &str) -> String {
+    let mut out = format!("{} {}\n", name, text.lines().count());
+    for line in text.lines() {
+        out += line;
+        out.push('\n');
+    }
+    out
+}
+
+impl Replay {
+    pub fn new(seed: u64, generator: Generator) -> Replay {
+        Replay {
+            seed: seed,
+            generator: generator,
+            gen_params: LevelGenParams::default(),
+            level: None,
+            snapshot: None,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn record_frame(&mut self, dt: f64, commands: &[Command]) {
+        self.frames.push(ReplayFrame {dt: dt, commands: commands.to_vec()});
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut out = header("replay", REPLAY_VERSION) + &format!("seed {} {}\n", self.seed, self.generator.name());
+        if self.gen_params != LevelGenParams::default() {
+            out += &serialize_block("levelgen", &self.gen_params.serialize());
+        }
+        if let Some(level) = &self.level {
+            out += &serialize_block("level", level);
+        }
+        if let Some(snapshot) = &self.snapshot {
+            out += &serialize_block("snapshot", snapshot);
+        }
+        for frame in self.frames.iter() {
+            out += &format!("frame {} {}\n", frame.dt, frame.commands.len());
+            for command in frame.commands.iter() {
+                out += &match command {
+                    Command::Walk(id, dir) => format!("walk {} {} {}\n", id, dir.x, dir.y),
+                    Command::Look(id, dir) => format!("look {} {} {}\n", id, dir.x, dir.y),
+                    Command::Shoot(id) => format!("shoot {}\n", id),
+                    Command::Eat(id) => format!("eat {}\n", id),
+                    Command::Throw(id) => format!("throw {}\n", id),
+                };
+            }
+        }
+        out
+    }
+
+    pub fn deserialize(text: &str) -> io::Result<Replay> {
+        let lines: Vec<&str> = text.lines().collect();
+        let mut at = 0;
+        let next_line = |at: &mut usize| -> io::Result<(usize, &str)> {
+            *at += 1;
+            match lines.get(*at - 1) {
+                Some(line) => Ok((*at, *line)),
+                None => Err(bad_data("unexpected end of replay".to_string())),
+            }
+        };
+        fn field<T: std::str::FromStr>(line_no: usize, tokens: &[&str], i: usize) -> io::Result<T> {
+            tokens.get(i)
+                .and_then(|t| t.parse().ok())
+                .ok_or_else(|| bad_data(format!("line {}: bad field {}", line_no, i)))
+        }
+
+        check_header(Some(next_line(&mut at)?.1), "replay", &[1, REPLAY_VERSION])?;
+
+        let (line_no, seed_line) = next_line(&mut at)?;
+        let seed_line: Vec<&str> = seed_line.split_whitespace().collect();
+        if seed_line.first() != Some(&"seed") {
+            return Err(bad_data(format!("line {}: expected seed", line_no)));
+        }
+        let generator = match seed_line.get(2) {
+            Some(name) => Generator::from_name(name).ok_or_else(|| bad_data(format!("line {}: unknown generator {}", line_no, name)))?,
+            None => Generator::Drunk,
+        };
+        let mut replay = Replay::new(field(line_no, &seed_line, 1)?, generator);
+
+        while at < lines.len() {
+            let (line_no, line) = next_line(&mut at)?;
+            let t: Vec<&str> = line.split_whitespace().collect();
+            if t.is_empty() { continue; }
+
+            // levelgen, level and snapshot blocks are checked here so playback cant fail halfway
+            if t[0] == "levelgen" || t[0] == "level" || t[0] == "snapshot" {
+                let n_lines: usize = field(line_no, &t, 1)?;
+                let mut block = String::new();
+                for _ in 0..n_lines {
+                    block += next_line(&mut at)?.1;
+                    block.push('\n');
+                }
+                let bad_block = |e: io::Error| bad_data(format!("line {}: bad {}: {}", line_no, t[0], e));
+                match t[0] {
+                    "levelgen" => replay.gen_params = LevelGenParams::deserialize(&block).map_err(bad_block)?,
+                    "level" => {
+                        parse_level(&block, replay.seed).map_err(bad_block)?;
+                        replay.level = Some(block);
+                    },
+                    _ => {
+                        SimulationState::deserialize(&block).map_err(bad_block)?;
+                        replay.snapshot = Some(block);
+                    },
+                }
+                continue;
+            }
+
+            if t[0] != "frame" {
+                return Err(bad_data(format!("line {}: expected frame", line_no)));
+            }
+            let dt = field(line_no, &t, 1)?;
+            let n_commands: usize = field(line_no, &t, 2)?;
+
+            let mut commands = Vec::new();
+            for _ in 0..n_commands {
+                let (line_no, line) = next_line(&mut at)?;
+                let t: Vec<&str> = line.split_whitespace().collect();
+                let command = match t.first() {
+                    Some(&"walk") => Command::Walk(field(line_no, &t, 1)?, Vec2::new(field(line_no, &t, 2)?, field(line_no, &t, 3)?)),
+                    Some(&"look") => Command::Look(field(line_no, &t, 1)?, Vec2::new(field(line_no, &t, 2)?, field(line_no, &t, 3)?)),
+                    Some(&"shoot") => Command::Shoot(field(line_no, &t, 1)?),
+                    Some(&"eat") => Command::Eat(field(line_no, &t, 1)?),
+                    Some(&"throw") => Command::Throw(field(line_no, &t, 1)?),
+                    _ => return Err(bad_data(format!("line {}: unknown command", line_no))),
+                };
+                commands.push(command);
+            }
+            replay.frames.push(ReplayFrame {dt: dt, commands: commands});
+        }
+
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.serialize())
+    }
+
+    pub fn load(path: &str) -> io::Result<Replay> {
+        Replay::deserialize(&fs::read_to_string(path)?)
+    }
+}
+
+#[test]
+fn test_replay_round_trip() {
+    let mut replay = Replay::new(1234, Generator::Cave);
+    replay.record_frame(1.0 / 60.0, &[Command::Walk(7, Vec2::new(0.1, -0.3).normalize()), Command::Shoot(7)]);
+    replay.record_frame(0.0234, &[]);
+    replay.record_frame(1.0 / 3.0, &[Command::Look(u32::MAX, Vec2::new(1.0 / 3.0, 2.0 / 3.0))]);
+    replay.record_frame(0.01, &[Command::Eat(7), Command::Throw(7)]);
+
+    assert_eq!(Replay::deserialize(&replay.serialize()).unwrap(), replay);
+
+    replay.gen_params.side_length = 25;
+    replay.level = Some("#####\n#p..#\n#####\n".to_string());
+    assert_eq!(Replay::deserialize(&replay.serialize()).unwrap(), replay);
+    assert!(Replay::deserialize("rustlandkings replay 2\nseed 1\nlevel 2\n#####\n#...#\n").is_err());
+    assert_eq!(Replay::deserialize("rustlandkings replay 1\nseed 5\n").unwrap(), Replay::new(5, Generator::Drunk));
+    assert!(Replay::deserialize("rustlandkings replay 999\nseed 1\n").is_err());
+    assert_eq!(Replay::deserialize("rustlandkings replay 2\nseed 5\n").unwrap(), Replay::new(5, Generator::Drunk));
+    assert!(Replay::deserialize("rustlandkings replay 1\nseed 1\nframe 0.1 1\n").is_err());
+    assert!(Replay::deserialize("rustlandkings replay 1\nseed 1\nframe 0.1 1\nreload 7\n").is_err());
+}
@@ -46,6 +46,7 @@ impl ScreenTransform {
         }
     }
 
+    #[cfg(feature = "sdl")]
     pub fn sdl_rect(&self, r: Rect) -> sdl2::rect::Rect {
         sdl2::rect::Rect::new(
             (r.x * self.px.0 as f32 / self.aspect_ratio()) as i32,
@@ -2,13 +2,15 @@ use crate::entity::*;
 use crate::simulation_state::*;
 use crate::vec2::*;
 use std::f32::consts::PI;
-use rand::Rng;
 
 #[derive(Clone, Copy)]
 pub enum SideEffect {
     None,
     Damage(f32, u32),                               // damage subject
+    Heal(f32, f32, u32),                            // amount, max health, subject
+    Explosion(Vec2, f32, f32, EntityForce),         // pos, radius, damage, force (spares its own side)
     SpawnCircBullets(i32, f32, EntityForce, Vec2, u32),  // n, damage, force, pos, source
+    PickUp(u32, u32),                               // picker, item
 
 }
 
@@ -17,21 +19,74 @@ impl SimulationState {
         match effect {
             SideEffect::Damage(amount, subject) => {
                 if let Some(mut entity) = self.entities.get_mut(&subject) {
-                    entity.health -= amount;
+                    if !entity.is_shielded(self.time as f32) {
+                        entity.health -= amount;
+                    }
                 } else {
                     // maybe trace this if theres a suspicion its wrong, it shouldnt really fail
                     panic!("shouldnt happen");
                 }
             },
+            SideEffect::Heal(amount, max_health, subject) => {
+                if let Some(entity) = self.entities.get_mut(&subject) {
+                    entity.health = (entity.health + amount).min(max_health.max(entity.health));
+                }
+            },
+            SideEffect::Explosion(pos, radius, damage, force) => {
+                let time = self.time as f32;
+                for entity in self.entities.values_mut() {
+                    if entity.force == force || entity.collision_group == CollisionGroup::Bullet { continue; }
+                    // nearest point of the aabb, so big things get caught by their edges
+                    let nearest = Vec2::new(pos.x.max(entity.aabb.left()).min(entity.aabb.right()), pos.y.max(entity.aabb.top()).min(entity.aabb.bot()));
+                    // walls soak it up, if the middle of something is round a corner it doesnt get hit
+                    let behind_wall = self.terrain.raycast(pos, entity.aabb.center()).is_some();
+                    if nearest.sub(pos).magnitude() <= radius && !behind_wall && !entity.is_shielded(time) {
+                        entity.health -= damage;
+                    }
+                }
+            },
             SideEffect::SpawnCircBullets(n_bullets, _damage, force, pos, source) => {
                 for i in 0..n_bullets {
                     let i_frac = i as f32 / n_bullets as f32;
                     let dirn_vec = Vec2::new((i_frac * 2.0*PI).sin(),(i_frac * 2.0*PI).cos());
                     
-                    self.entities.insert(rand::thread_rng().gen(), Entity::new_bullet(pos, dirn_vec, force, source));
+                    self.entities.insert(new_entity_id(&self.entities, &mut self.rng), Entity::new_bullet(pos, dirn_vec, force, source));
+                }
+            },
+            SideEffect::PickUp(picker, item) => {
+                // two things touching it in one frame, first one gets it
+                let variety = match self.entities.get(&item) {
+                    Some(e) if e.health > 0.0 => e.variety,
+                    _ => return,
+                };
+                match variety {
+                    EntityType::Key => {
+                        for door in self.entities.values_mut().filter(|e| e.variety == EntityType::Door) {
+                            door.health = 0.0;
+                        }
+                    },
+                    EntityType::GunPickup(kind) => self.pick_up_gun(picker, kind.gun()),
+                    _ => {},
                 }
+                self.entities.get_mut(&item).unwrap().health = 0.0;
             },
             SideEffect::None => {},
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_explosion_behind_wall() {
+    use crate::level::*;
+
+    let mut state = parse_level("########\n#p.e#e.#\n########\n", 1).unwrap();
+    let at = |x: i32| state.terrain.get_rect_2d(x, 1).center();
+    let id_at = |state: &SimulationState, x: i32| *state.entities.iter().find(|(_, e)| state.terrain.get_xy_of_position(e.aabb.center()) == (x, 1)).unwrap().0;
+    let (player, near, far) = (id_at(&state, 1), id_at(&state, 3), id_at(&state, 5));
+
+    // both enemies in range, but theres a wall in the way of the far one
+    state.resolve_side_effect(SideEffect::Explosion(at(2), 4.0 * LEVEL_TILE_SIZE, 1.0, EntityForce::Player));
+    assert!(state.entities[&near].health < Entity::new_enemy(0.0, 0.0).health);
+    assert_eq!(state.entities[&far].health, Entity::new_enemy(0.0, 0.0).health);
+    assert_eq!(state.entities[&player].health, PLAYER_MAX_HEALTH);
+}
@@ -1,14 +1,28 @@
 use crate::grid::*;
 use crate::entity::*;
-use std::collections::HashMap;
+use crate::generators::params::*;
+use crate::generators::placement::*;
+use crate::pathfinding::*;
+use crate::systems::projectiles::*;
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
 use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 
 
 pub struct SimulationState {
     pub time: f64,
     pub dt: f64,
     pub terrain: Grid,
-    pub entities: HashMap<u32, Entity>,
+    pub entities: BTreeMap<u32, Entity>,    // btree so iteration order is deterministic
+    pub inventories: BTreeMap<u32, VecDeque<Gun>>,  // guns lined up behind the one an entity has out, next first
+    pub spawn_report: SpawnReport,  // what the generator had to move or drop to fit its spawns in. not saved in snapshots
+
+    // everything random in the sim goes through this, so a seed + commands reproduces a run
+    // chacha because its stream position can be saved and restored
+    pub seed: u64,
+    pub rng: ChaCha8Rng,
 }
 
 fn generate_level() -> Grid {
@@ -32,12 +46,14 @@ struct Walker {
     alive: bool,
 }
 
-pub fn generate_level_drunk() -> SimulationState {
-    let side_length = 40;
-    let elem_size = 0.2;
-    let num_walkers = 40;
-    let walk_iters = 20;
-    let p_change_dir = 0.3;
+pub fn generate_level_drunk(seed: u64, params: &LevelGenParams) -> SimulationState {
+    let side_length = params.side_length;
+    let elem_size = params.elem_size;
+    let num_walkers = params.num_walkers;
+    let walk_iters = params.walk_iters;
+    let p_change_dir = params.p_change_dir;
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
 
     let mut g = Grid::new(side_length, side_length, elem_size, elem_size);
     let mut walkers = Vec::new();
@@ -49,16 +65,14 @@ pub fn generate_level_drunk() -> SimulationState {
             pos: (side_length/2, side_length/2),
             /*
             pos: 
-                (rand::thread_rng().gen_range(1..side_length-1),
-                rand::thread_rng().gen_range(1..side_length-1)),
+                (rng.gen_range(1..side_length-1),
+                rng.gen_range(1..side_length-1)),
             */
-            dir: rand::thread_rng().gen_range(0..4),
+            dir: rng.gen_range(0..4),
             alive: true,
         });
     }
 
-    // Y entities spawn in walls???
-
     for _ in 1..walk_iters {
         for w in walkers.iter_mut() {
             g.set_2d(w.pos.0, w.pos.1, Tile::Ground);
@@ -67,8 +81,8 @@ pub fn generate_level_drunk() -> SimulationState {
             }
 
             // maybe change direction
-            if rand::thread_rng().gen_range(0.0..1.0) < p_change_dir {
-                let mut idx = rand::thread_rng().gen_range(0..3);
+            if rng.gen_range(0.0..1.0) < p_change_dir {
+                let mut idx = rng.gen_range(0..3);
                 if idx >= w.dir {
                     idx += 1;
                 }
@@ -96,8 +110,6 @@ pub fn generate_level_drunk() -> SimulationState {
         g.set_2d(w.pos.0, w.pos.1, Tile::Ground);
     }
 
-    let mut entities = HashMap::new();
-    
     let (player_walker_i, player_walker) = walkers.iter()
         .enumerate()
         .max_by_key(|(i, w1)| {
@@ -106,62 +118,131 @@ pub fn generate_level_drunk() -> SimulationState {
             x*x+y*y
         }).unwrap();
 
-    let player_pos = g.get_rect_2d(player_walker.pos.0, player_walker.pos.1).center();
-    entities.insert(rand::thread_rng().gen(), Entity::new_player(player_pos.x, player_pos.y));
-
-    for (i, w) in walkers.iter().enumerate() {
-        if i == player_walker_i {continue};
-        let walker_pos = g.get_rect_2d(w.pos.0, w.pos.1).center();
-                                                                        
-        entities.insert(rand::thread_rng().gen(), match rand::thread_rng().gen_range(0..6) {
-            0 => {Entity::new_enemy(walker_pos.x, walker_pos.y)}
-            1 => {Entity::new_crate(walker_pos.x, walker_pos.y)}
-            2 => {Entity::new_retalliator(walker_pos.x, walker_pos.y)}
-            3 => {Entity::new_swarmer(walker_pos.x, walker_pos.y)}
-            4 => {Entity::new_sprayer(walker_pos.x, walker_pos.y)}
-            5 => {Entity::new_bigdog(walker_pos.x, walker_pos.y)}
-            _ => {panic!("shouldnt happen")},
-        });
+    let spawn_tiles: Vec<(i32, i32)> = walkers.iter()
+        .enumerate()
+        .filter(|(i, _)| *i != player_walker_i)
+        .map(|(_, w)| w.pos)
+        .collect();
+
+    return populate_level(g, player_walker.pos, &spawn_tiles, &[], params, rng, seed);
+}
+
+// tiles at least min_distance walk from the player, in the order given. ones it cant reach are kept,
+// reachability is the generators problem
+pub fn far_enough_from(g: &Grid, player_tile: (i32, i32), tiles: &[(i32, i32)], min_distance: f32) -> Vec<(i32, i32)> {
+    let flow_field = FlowField::new(g, player_tile);
+    tiles.iter()
+        .filter(|(x, y)| flow_field.distance(*x, *y).map(|d| d >= min_distance).unwrap_or(true))
+        .copied()
+        .collect()
+}
+
+
+// fresh id for a new entity. ids come off the rng so theyre reproducible, this just makes sure
+// a repeat cant replace something
+pub fn new_entity_id(entities: &BTreeMap<u32, Entity>, rng: &mut impl Rng) -> u32 {
+    loop {
+        let id = rng.gen();
+        if !entities.contains_key(&id) {
+            return id;
+        }
+    }
+}
+
+// player goes on player_tile, something off the spawn table on each of spawn_tiles
+// that isnt too close to walk to, then the generators own fixtures (doors, keys, guards).
+// then anything that doesnt fit gets moved (or dropped if theres no room), and spawns that
+// got moved too close get dropped. fixtures are where the generator wants them so they stay
+pub fn populate_level(g: Grid, player_tile: (i32, i32), spawn_tiles: &[(i32, i32)], fixtures: &[Entity], params: &LevelGenParams, mut rng: ChaCha8Rng, seed: u64) -> SimulationState {
+    let mut entities = BTreeMap::new();
+
+    let player_pos = g.get_rect_2d(player_tile.0, player_tile.1).center();
+    entities.insert(new_entity_id(&entities, &mut rng), Entity::new_player(player_pos.x, player_pos.y));
+
+    let mut spawned = Vec::new();
+    for tile in far_enough_from(&g, player_tile, spawn_tiles, params.min_spawn_distance).iter() {
+        let spawn_pos = g.get_rect_2d(tile.0, tile.1).center();
+        let id = new_entity_id(&entities, &mut rng);
+        if let Some(entity) = params.spawn(&mut rng, spawn_pos.x, spawn_pos.y) {
+            entities.insert(id, entity);
+            spawned.push(id);
+        }
+    }
+    for fixture in fixtures.iter() {
+        entities.insert(new_entity_id(&entities, &mut rng), *fixture);
     }
 
-    return SimulationState {
+    let mut state = SimulationState {
         time: 0.0,
         dt: 0.016,
         entities: entities,
+        inventories: BTreeMap::new(),
+        spawn_report: SpawnReport::default(),
         terrain: g,
+        seed: seed,
+        rng: rng,
     };
+    state.spawn_report = fix_spawns(&mut state);
 
+    let flow_field = FlowField::new(&state.terrain, player_tile);
+    for id in spawned.iter() {
+        let (x, y) = match state.entities.get(id) {
+            Some(e) => state.terrain.get_xy_of_position(e.aabb.center()),
+            None => continue,
+        };
+        if flow_field.distance(x, y).map(|d| d < params.min_spawn_distance).unwrap_or(false) {
+            let entity = state.entities.remove(id).unwrap();
+            state.spawn_report.too_close.push(entity.variety);
+        }
+    }
+    return state;
 }
 
 impl SimulationState {
-    pub fn new() -> SimulationState {
+    pub fn new(seed: u64) -> SimulationState {
         let mut state = SimulationState {
             time: 0.0,
             dt: 0.016,
             terrain: generate_level(),
-            entities: HashMap::new(),
+            entities: BTreeMap::new(),
+            inventories: BTreeMap::new(),
+            spawn_report: SpawnReport::default(),
+            seed: seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
         };
 
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_player(0.4, 0.4));
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_enemy(0.8, 1.1));
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_enemy(1.5, 0.5));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_player(0.4, 0.4));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_enemy(0.8, 1.1));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_enemy(1.5, 0.5));
 
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_crate(0.8, 0.4));
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_crate(0.8, 0.9));
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_crate(1.0, 0.2));
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_crate(1.0, 0.4));
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_crate(1.0, 0.9));
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_crate(1.2, 0.6));
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_crate(1.2, 0.7));
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_crate(1.6, 1.0));
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_crate(1.6, 1.6));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_crate(0.8, 0.4));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_crate(0.8, 0.9));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_crate(1.0, 0.2));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_crate(1.0, 0.4));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_crate(1.0, 0.9));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_crate(1.2, 0.6));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_crate(1.2, 0.7));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_crate(1.6, 1.0));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_crate(1.6, 1.6));
 
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_retalliator(0.3, 1.6));
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_retalliator(1.0, 1.0));
-        state.entities.insert(rand::thread_rng().gen(), Entity::new_retalliator(0.9, 1.5));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_retalliator(0.3, 1.6));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_retalliator(1.0, 1.0));
+        state.entities.insert(new_entity_id(&state.entities, &mut state.rng), Entity::new_retalliator(0.9, 1.5));
 
 
 
         return state;
     }
+
+    // every removal goes through here so queued guns go with whoever was holding them
+    pub fn retain_entities(&mut self, mut keep: impl FnMut(&u32, &Entity) -> bool) {
+        let inventories = &mut self.inventories;
+        self.entities.retain(|id, e| {
+            let kept = keep(id, e);
+            if !kept {
+                inventories.remove(id);
+            }
+            kept
+        });
+    }
 }
\ No newline at end of file
@@ -0,0 +1,360 @@
+use crate::entity::*;
+use crate::grid::*;
+use crate::rect::*;
+use crate::vec2::*;
+use crate::simulation_state::*;
+use crate::generators::placement::SpawnReport;
+use crate::systems::projectiles::*;
+use crate::color::*;
+use crate::textformat::*;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+
+/*
+the whole SimulationState as text, enough to carry on exactly where it was
+
+rustlandkings snapshot 2
+time <time> <dt>
+rng <seed> <word pos>
+grid <w> <h> <elem w> <elem h>
+#####
+#...#       one row per line, # wall . ground
+#####
+entity <id> <force> <group> <variety> <source> <x> <y> <w> <h> <r> <g> <b> <a> <vx> <vy> <draw order> <health> <last hit> <speed> <melee damage> <look x> <look y> <boost until> <shield until>
+gun <damage> <spread> <speed> <last fired> <num bullets> <automatic> <cooldown> <ammo> <burst> <burst count> <burst cooldown> <keep shooting> <eat effect> <eat seconds>
+...
+queued <id>     one for each gun in an entitys inventory, in order, after all the entities
+gun ...
+
+version 1 has no boost until, shield until, eat effect or eat seconds. they load as 0 and no effect.
+it has no queued lines either, theyre only written when someone has guns queued and a version 1
+file with them is rejected
+*/
+
+const SNAPSHOT_VERSION: u32 = 2;
+
+// walks the whitespace separated fields of one line
+struct Fields<'a> {
+    line_no: usize,
+    tokens: std::str::SplitWhitespace<'a>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(line_no: usize, line: &'a str, keyword: &str) -> io::Result<Fields<'a>> {
+        let mut fields = Fields { line_no: line_no, tokens: line.split_whitespace() };
+        if fields.tokens.next() != Some(keyword) {
+            return Err(bad_data(format!("line {}: expected {}", line_no, keyword)));
+        }
+        Ok(fields)
+    }
+
+    fn next_str(&mut self) -> io::Result<&'a str> {
+        let line_no = self.line_no;
+        self.tokens.next().ok_or_else(|| bad_data(format!("line {}: missing field", line_no)))
+    }
+
+    fn next<T: FromStr>(&mut self) -> io::Result<T> {
+        let token = self.next_str()?;
+        token.parse().map_err(|_| bad_data(format!("line {}: bad field {}", self.line_no, token)))
+    }
+
+    fn next_bool(&mut self) -> io::Result<bool> {
+        self.next_enum(parse_flag)
+    }
+
+    fn next_enum<T>(&mut self, parse: fn(&str) -> Option<T>) -> io::Result<T> {
+        let token = self.next_str()?;
+        parse(token).ok_or_else(|| bad_data(format!("line {}: unknown {}", self.line_no, token)))
+    }
+}
+
+fn parse_force(s: &str) -> Option<EntityForce> {
+    match s {
+        "Player" => Some(EntityForce::Player),
+        "Neutral" => Some(EntityForce::Neutral),
+        "Enemy" => Some(EntityForce::Enemy),
+        _ => None,
+    }
+}
+
+fn parse_collision_group(s: &str) -> Option<CollisionGroup> {
+    match s {
+        "Bullet" => Some(CollisionGroup::Bullet),
+        "Static" => Some(CollisionGroup::Static),
+        "Other" => Some(CollisionGroup::Other),
+        "Item" => Some(CollisionGroup::Item),
+        _ => None,
+    }
+}
+
+pub fn parse_entity_type(s: &str) -> Option<EntityType> {
+    match s {
+        "Player" => Some(EntityType::Player),
+        "Bullet" => Some(EntityType::Bullet),
+        "Crate" => Some(EntityType::Crate),
+        "Retaliator" => Some(EntityType::Retaliator),
+        "Enemy" => Some(EntityType::Enemy),
+        "Swarmer" => Some(EntityType::Swarmer),
+        "Sprayer" => Some(EntityType::Sprayer),
+        "Bigdog" => Some(EntityType::Bigdog),
+        "ThrownGun" => Some(EntityType::ThrownGun),
+        "Key" => Some(EntityType::Key),
+        "Door" => Some(EntityType::Door),
+        // written with {:?} like the rest, eg. GunPickup(BurstRifle)
+        _ => s.strip_prefix("GunPickup(").and_then(|s| s.strip_suffix(')'))
+            .and_then(|name| ALL_GUN_KINDS.iter().find(|k| format!("{:?}", k) == name))
+            .map(|k| EntityType::GunPickup(*k)),
+    }
+}
+
+fn parse_draw_order(s: &str) -> Option<DrawOrder> {
+    match s {
+        "Front" => Some(DrawOrder::Front),
+        "Back" => Some(DrawOrder::Back),
+        _ => None,
+    }
+}
+
+fn serialize_gun(g: &Gun) -> String {
+    format!("gun {} {} {} {} {} {} {} {} {} {} {} {} {} {}\n",
+        g.damage, g.spread, g.speed, g.last_fired, g.num_bullets, g.automatic as u8, g.cooldown,
+        g.ammo, g.burst, g.burst_count, g.burst_cooldown, g.keep_shooting as u8,
+        g.eat_effect.name(), g.eat_effect.duration())
+}
+
+fn deserialize_gun(version: u32, line_no: usize, gun_line: &str) -> io::Result<Gun> {
+    let mut g = Fields::new(line_no, gun_line, "gun")?;
+    Ok(Gun {
+        damage: g.next()?,
+        spread: g.next()?,
+        speed: g.next()?,
+        last_fired: g.next()?,
+        num_bullets: g.next()?,
+        automatic: g.next_bool()?,
+        cooldown: g.next()?,
+        ammo: g.next()?,
+        burst: g.next()?,
+        burst_count: g.next()?,
+        burst_cooldown: g.next()?,
+        keep_shooting: g.next_bool()?,
+        eat_effect: if version < 2 { EatEffect::None } else {
+            let name = g.next_str()?;
+            EatEffect::from_name(name, g.next()?).ok_or_else(|| bad_data(format!("line {}: unknown eat effect {}", line_no, name)))?
+        },
+    })
+}
+
+fn serialize_entity(id: u32, e: &Entity) -> String {
+    format!("entity {} {:?} {:?} {:?} {} {} {} {} {} {} {} {} {} {} {} {:?} {} {} {} {} {} {} {} {}\n{}",
+        id, e.force, e.collision_group, e.variety, e.source,
+        e.aabb.x, e.aabb.y, e.aabb.w, e.aabb.h,
+        e.colour.r, e.colour.g, e.colour.b, e.colour.a,
+        e.velocity.x, e.velocity.y, e.draw_order, e.health, e.last_hit, e.speed, e.melee_damage,
+        e.look_direction.x, e.look_direction.y, e.boost_until, e.shield_until,
+        serialize_gun(&e.gun))
+}
+
+fn deserialize_entity(version: u32, line_no: usize, entity_line: &str, gun_line: &str) -> io::Result<(u32, Entity)> {
+    let mut f = Fields::new(line_no, entity_line, "entity")?;
+    let id = f.next()?;
+    let force = f.next_enum(parse_force)?;
+    let collision_group = f.next_enum(parse_collision_group)?;
+    let variety = f.next_enum(parse_entity_type)?;
+    let source = f.next()?;
+    let aabb = Rect::new(f.next()?, f.next()?, f.next()?, f.next()?);
+    let colour = Color::RGBA(f.next()?, f.next()?, f.next()?, f.next()?);
+    let velocity = Vec2::new(f.next()?, f.next()?);
+    let draw_order = f.next_enum(parse_draw_order)?;
+    let health = f.next()?;
+    let last_hit = f.next()?;
+    let speed = f.next()?;
+    let melee_damage = f.next()?;
+    let look_direction = Vec2::new(f.next()?, f.next()?);
+    let (boost_until, shield_until) = if version < 2 { (0.0, 0.0) } else { (f.next()?, f.next()?) };
+
+    let gun = deserialize_gun(version, line_no + 1, gun_line)?;
+
+    Ok((id, Entity {
+        force: force,
+        collision_group: collision_group,
+        variety: variety,
+        source: source,
+        aabb: aabb,
+        colour: colour,
+        velocity: velocity,
+        draw_order: draw_order,
+        health: health,
+        last_hit: last_hit,
+        boost_until: boost_until,
+        shield_until: shield_until,
+        speed: speed,
+        melee_damage: melee_damage,
+        look_direction: look_direction,
+        gun: gun,
+    }))
+}
+
+impl SimulationState {
+    pub fn serialize(&self) -> String {
+        let mut out = header("snapshot", SNAPSHOT_VERSION);
+        out += &format!("time {} {}\n", self.time, self.dt);
+        out += &format!("rng {} {}\n", self.seed, self.rng.get_word_pos());
+
+        let t = &self.terrain;
+        out += &format!("grid {} {} {} {}\n", t.w, t.h, t.elem_w, t.elem_h);
+        for row in t.tiles.chunks(t.w as usize) {
+            out.extend(row.iter().map(|tile| match tile {
+                Tile::Wall => '#',
+                Tile::Ground => '.',
+            }));
+            out.push('\n');
+        }
+
+        for (id, entity) in self.entities.iter() {
+            out += &serialize_entity(*id, entity);
+        }
+        for (id, queue) in self.inventories.iter() {
+            for gun in queue.iter() {
+                out += &format!("queued {}\n{}", id, serialize_gun(gun));
+            }
+        }
+        out
+    }
+
+    pub fn deserialize(text: &str) -> io::Result<SimulationState> {
+        let lines: Vec<&str> = text.lines().collect();
+        let line = |i: usize| lines.get(i).copied().ok_or_else(|| bad_data("unexpected end of snapshot".to_string()));
+
+        let version = check_header(lines.first().copied(), "snapshot", &[1, SNAPSHOT_VERSION])?;
+
+        let mut f = Fields::new(2, line(1)?, "time")?;
+        let time = f.next()?;
+        let dt = f.next()?;
+
+        let mut f = Fields::new(3, line(2)?, "rng")?;
+        let seed = f.next()?;
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_word_pos(f.next()?);
+
+        let mut f = Fields::new(4, line(3)?, "grid")?;
+        let mut terrain = Grid::new(f.next()?, f.next()?, f.next()?, f.next()?);
+        for y in 0..terrain.h {
+            let line_no = 5 + y as usize;
+            let row: Vec<char> = line(line_no - 1)?.chars().collect();
+            if row.len() != terrain.w as usize {
+                return Err(bad_data(format!("line {}: grid row should be {} wide", line_no, terrain.w)));
+            }
+            for (x, c) in row.iter().enumerate() {
+                terrain.set_2d(x as i32, y, match c {
+                    '#' => Tile::Wall,
+                    '.' => Tile::Ground,
+                    _ => return Err(bad_data(format!("line {}: unknown tile {}", line_no, c))),
+                });
+            }
+        }
+
+        let mut entities = BTreeMap::new();
+        let mut inventories: BTreeMap<u32, VecDeque<Gun>> = BTreeMap::new();
+        let mut i = 4 + terrain.h as usize;
+        while i < lines.len() {
+            if lines[i].trim().is_empty() {
+                i += 1;
+                continue;
+            }
+            if lines[i].starts_with("queued") {
+                if version < 2 {
+                    return Err(bad_data(format!("line {}: queued guns need snapshot version 2", i + 1)));
+                }
+                let id = Fields::new(i + 1, lines[i], "queued")?.next()?;
+                inventories.entry(id).or_default().push_back(deserialize_gun(version, i + 2, line(i + 1)?)?);
+            } else {
+                let (id, entity) = deserialize_entity(version, i + 1, lines[i], line(i + 1)?)?;
+                entities.insert(id, entity);
+            }
+            i += 2;
+        }
+
+        Ok(SimulationState {
+            time: time,
+            dt: dt,
+            terrain: terrain,
+            entities: entities,
+            inventories: inventories,
+            spawn_report: SpawnReport::default(),
+            seed: seed,
+            rng: rng,
+        })
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.serialize())
+    }
+
+    pub fn load(path: &str) -> io::Result<SimulationState> {
+        SimulationState::deserialize(&fs::read_to_string(path)?)
+    }
+}
+
+#[test]
+fn test_snapshot_round_trip() {
+    use crate::systems::command::*;
+    use crate::generators::params::*;
+    use rand::Rng;
+
+    let mut state = generate_level_drunk(77, &LevelGenParams::default());
+    let (player_id, _) = state.entities.iter().find(|(_, e)| e.variety == EntityType::Player).unwrap();
+    let player_id = *player_id;
+    state.time = 1.25;
+    apply_command(&mut state, Command::Walk(player_id, Vec2::new(0.6, 0.8)));
+    apply_command(&mut state, Command::Shoot(player_id));
+    state.pick_up_gun(player_id, Gun::new_shotgun());
+    state.pick_up_gun(player_id, Gun::new_makina());
+    let pickup_id = new_entity_id(&state.entities, &mut state.rng);
+    state.entities.insert(pickup_id, Entity::new_gun_pickup(1.0, 1.0, GunKind::BurstRifle));
+    apply_command(&mut state, Command::Eat(player_id));
+
+    let text = state.serialize();
+    let mut loaded = SimulationState::deserialize(&text).unwrap();
+    assert_eq!(loaded.serialize(), text);
+    assert_eq!(format!("{:?}", loaded.entities), format!("{:?}", state.entities));
+    assert_eq!(loaded.terrain.tiles, state.terrain.tiles);
+    assert_eq!(format!("{:?}", loaded.guns(player_id)), format!("{:?}", state.guns(player_id)));
+
+    // rng carries on from the same place
+    assert_eq!(loaded.rng.gen::<u64>(), state.rng.gen::<u64>());
+
+    assert!(SimulationState::deserialize("rustlandkings snapshot 1\n").is_err());
+    assert!(SimulationState::deserialize(&text.replace("gun ", "gnu ")).is_err());
+}
+
+#[test]
+fn test_load_v1_snapshot() {
+    let text = "\
+rustlandkings snapshot 1
+time 2.5 0.016
+rng 9 40
+grid 3 3 0.2 0.2
+###
+#.#
+###
+entity 7 Player Other Player 0 0.25 0.25 0.1 0.1 0 255 0 255 0 0 Front 5 0 0.6 0 1 0
+gun 1 0.1 3 0 1 0 0.3 24 1 0 0 0
+";
+    let state = SimulationState::deserialize(text).unwrap();
+    let player = state.entities[&7];
+    assert_eq!(player.health, 5.0);
+    assert_eq!((player.boost_until, player.shield_until), (0.0, 0.0));
+    assert_eq!(player.gun.ammo, 24);
+    assert_eq!(player.gun.eat_effect, EatEffect::None);
+
+    // saved again its the current version
+    assert!(state.serialize().starts_with(&format!("rustlandkings snapshot {}\n", SNAPSHOT_VERSION)));
+    assert!(SimulationState::deserialize(&state.serialize()).is_ok());
+    assert!(SimulationState::deserialize(&(text.to_string() + "queued 7\ngun 1 0.1 3 0 1 0 0.3 24 1 0 0 0\n")).is_err());
+}
@@ -1,8 +1,22 @@
 use crate::systems::command::*;
 use crate::simulation_state::*;
 use crate::entity::*;
+use crate::vec2::*;
+use crate::pathfinding::*;
 
-pub fn compute_ai_commands(state: &SimulationState, commands: &mut Vec<Command>) {
+// straight at it if theres line of sight, otherwise along a path around the walls
+fn chase_dir(state: &SimulationState, pathfinder: &Pathfinder, enemy: &Entity, target: Vec2) -> Option<Vec2> {
+    let from = enemy.aabb.center();
+    if state.terrain.raycast(from, target) == None {
+        return Some(target.sub(from).normalize());
+    }
+    let path = pathfinder.find_path(&state.terrain, enemy.aabb, target)?;
+    let waypoint = path.iter().find(|p| p.sub(from).magnitude() > 0.02).copied().unwrap_or(target);
+    Some(waypoint.sub(from).normalize())
+}
+
+// flow_field leads to the player, swarmers follow it. pathfinder has to be made from state.terrain
+pub fn compute_ai_commands(state: &SimulationState, flow_field: &FlowField, pathfinder: &Pathfinder, commands: &mut Vec<Command>) {
     for (enemy_id, enemy) in state.entities.iter() {
         match enemy.variety {
             EntityType::Enemy => {
@@ -18,8 +32,13 @@ pub fn compute_ai_commands(state: &SimulationState, commands: &mut Vec<Command>)
             EntityType::Swarmer => {
                 for (target_id, target) in state.entities.iter().filter(|(_, e)| e.variety == EntityType::Player) {
                     let distance = enemy.aabb.center().sub(target.aabb.center()).magnitude();
-                    let dir = target.aabb.center().sub(enemy.aabb.center()).normalize();
-                    if distance < 1.0 && state.terrain.raycast(enemy.aabb.center(), target.aabb.center()) == None {
+                    if distance > 1.0 {continue};
+                    let dir = if state.terrain.raycast(enemy.aabb.center(), target.aabb.center()) == None {
+                        Some(target.aabb.center().sub(enemy.aabb.center()).normalize())
+                    } else {
+                        flow_field.direction(&state.terrain, enemy.aabb.center())
+                    };
+                    if let Some(dir) = dir {
                         commands.push(Command::Walk(*enemy_id, dir));
                     }
                 }
@@ -32,7 +51,12 @@ pub fn compute_ai_commands(state: &SimulationState, commands: &mut Vec<Command>)
                     let dir = target.aabb.center().sub(enemy.aabb.center()).normalize();
 
                     if distance > 1.0 {continue};
-                    if state.terrain.raycast(enemy.aabb.center(), target.aabb.center()) != None {continue};
+                    if state.terrain.raycast(enemy.aabb.center(), target.aabb.center()) != None {
+                        if let Some(dir) = chase_dir(state, pathfinder, enemy, target.aabb.center()) {
+                            commands.push(Command::Walk(*enemy_id, dir));
+                        }
+                        continue;
+                    };
 
                     if distance > engagement_range * 1.1 {
                         commands.push(Command::Walk(*enemy_id, dir));
@@ -52,7 +76,12 @@ pub fn compute_ai_commands(state: &SimulationState, commands: &mut Vec<Command>)
                     let dir = target.aabb.center().sub(enemy.aabb.center()).normalize();
 
                     if distance > 1.0 {continue};
-                    if state.terrain.raycast(enemy.aabb.center(), target.aabb.center()) != None {continue};
+                    if state.terrain.raycast(enemy.aabb.center(), target.aabb.center()) != None {
+                        if let Some(dir) = chase_dir(state, pathfinder, enemy, target.aabb.center()) {
+                            commands.push(Command::Walk(*enemy_id, dir));
+                        }
+                        continue;
+                    };
 
                     if distance > engagement_range * 1.1 {
                         commands.push(Command::Walk(*enemy_id, dir));
@@ -66,4 +95,24 @@ pub fn compute_ai_commands(state: &SimulationState, commands: &mut Vec<Command>)
             _ => {},
         }
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_swarmer_goes_around_walls() {
+    use crate::level::*;
+
+    let state = parse_level("\
+#######
+#p....#
+####..#
+#s....#
+#######
+", 1).unwrap();
+    let (swarmer_id, _) = state.entities.iter().find(|(_, e)| e.variety == EntityType::Swarmer).unwrap();
+
+    let mut commands = Vec::new();
+    compute_ai_commands(&state, &FlowField::new(&state.terrain, (1, 1)), &Pathfinder::new(&state.terrain), &mut commands);
+    match commands.iter().find(|c| matches!(c, Command::Walk(id, _) if id == swarmer_id)) {
+        Some(Command::Walk(_, dir)) => assert!(dir.x > 0.9, "walked {:?}", dir),
+        _ => panic!("swarmer didnt move"),
+    }
+}
@@ -0,0 +1,70 @@
+use crate::rect::*;
+
+use std::collections::HashMap;
+
+/*
+uniform grid for entity-entity collisions so each thing only gets tested against whats near it.
+cells are the terrain tile size, things go in every cell their aabb touches
+*/
+
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<u32>>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> SpatialHash {
+        SpatialHash {
+            cell_size: cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell_range(&self, r: Rect) -> (i32, i32, i32, i32) {
+        (
+            (r.left() / self.cell_size).floor() as i32,
+            (r.right() / self.cell_size).floor() as i32,
+            (r.top() / self.cell_size).floor() as i32,
+            (r.bot() / self.cell_size).floor() as i32,
+        )
+    }
+
+    pub fn insert(&mut self, id: u32, r: Rect) {
+        let (x0, x1, y0, y1) = self.cell_range(r);
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                self.cells.entry((x, y)).or_default().push(id);
+            }
+        }
+    }
+
+    // everything sharing a cell with r, in id order so results come out like iterating the whole map
+    pub fn query(&self, r: Rect) -> Vec<u32> {
+        let (x0, x1, y0, y1) = self.cell_range(r);
+        let mut found = Vec::new();
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                if let Some(ids) = self.cells.get(&(x, y)) {
+                    found.extend_from_slice(ids);
+                }
+            }
+        }
+        found.sort();
+        found.dedup();
+        found
+    }
+}
+
+#[test]
+fn test_spatial_hash() {
+    let mut hash = SpatialHash::new(0.2);
+    hash.insert(3, Rect::new(0.05, 0.05, 0.05, 0.05));
+    hash.insert(1, Rect::new(0.15, 0.15, 0.1, 0.1));    // straddles 4 cells
+    hash.insert(2, Rect::new(1.0, 1.0, 0.05, 0.05));
+    hash.insert(4, Rect::new(-0.1, -0.1, 0.05, 0.05));
+
+    assert_eq!(hash.query(Rect::new(0.0, 0.0, 0.1, 0.1)), vec![1, 3]);
+    assert_eq!(hash.query(Rect::new(0.3, 0.3, 0.05, 0.05)), vec![1]);
+    assert_eq!(hash.query(Rect::new(0.9, 0.9, 0.05, 0.05)), Vec::<u32>::new());
+    assert_eq!(hash.query(Rect::new(-0.15, -0.15, 0.1, 0.1)), vec![4]);
+}
@@ -1,19 +1,14 @@
 use crate::rect::*;
 use crate::entity::*;
 use crate::grid::*;
+use crate::vec2::*;
+use crate::systems::broadphase::*;
+#[cfg(test)]
+use crate::systems::testing::*;
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum CollisionDirection {
-    Above,
-    Left,
-    Right,
-    Below,
-    Bad,
-}
-
 #[derive(Debug, Clone, Copy)]
 pub enum CollisionObject {
     Entity(u32),
@@ -24,91 +19,160 @@ pub enum CollisionObject {
 pub struct CollisionEvent {
     pub subject: u32,
     pub object: CollisionObject,
-    pub dir: CollisionDirection,
+    pub toi: f32,           // how far through this frames movement they touch, 0 if they already overlap
+    pub normal: Vec2,       // out of the face of the object that got hit
     pub subject_rect: Rect,
     pub object_rect: Rect,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    pub toi: f32,
+    pub normal: Vec2,
+}
+
+// touching edges doesnt count
+fn overlapping(a: Rect, b: Rect) -> bool {
+    a.left() < b.right() && b.left() < a.right() && a.top() < b.bot() && b.top() < a.bot()
+}
+
+// a moving by delta against b sitting still. None if it misses or just slides along an edge.
+// if theyre already overlapping its toi 0 and the normal is the shortest way out
+pub fn swept_aabb(a: Rect, delta: Vec2, b: Rect) -> Option<Contact> {
+    if overlapping(a, b) {
+        let push = [
+            (b.right() - a.left(), Vec2::new(1.0, 0.0)),
+            (a.right() - b.left(), Vec2::new(-1.0, 0.0)),
+            (b.bot() - a.top(), Vec2::new(0.0, 1.0)),
+            (a.bot() - b.top(), Vec2::new(0.0, -1.0)),
+        ];
+        let (_, normal) = push.iter().fold(push[0], |best, p| if p.0 < best.0 { *p } else { best });
+        return Some(Contact {toi: 0.0, normal: normal});
+    }
+
+    // when they start and stop overlapping along one axis, as fractions of delta
+    let axis = |a_min: f32, a_max: f32, b_min: f32, b_max: f32, d: f32| -> Option<(f32, f32)> {
+        if d > 0.0 {
+            Some(((b_min - a_max) / d, (b_max - a_min) / d))
+        } else if d < 0.0 {
+            Some(((b_max - a_min) / d, (b_min - a_max) / d))
+        } else if a_max <= b_min || a_min >= b_max {
+            None
+        } else {
+            Some((-f32::INFINITY, f32::INFINITY))
+        }
+    };
+    let (x_entry, x_exit) = axis(a.left(), a.right(), b.left(), b.right(), delta.x)?;
+    let (y_entry, y_exit) = axis(a.top(), a.bot(), b.top(), b.bot(), delta.y)?;
+
+    let entry = x_entry.max(y_entry);
+    let exit = x_exit.min(y_exit);
+    if entry >= exit || !(0.0..=1.0).contains(&entry) {
+        return None;
+    }
+
+    // whichever axis lined up last is the side it came through
+    let normal = if x_entry >= y_entry {
+        Vec2::new(-delta.x.signum(), 0.0)
+    } else {
+        Vec2::new(0.0, -delta.y.signum())
+    };
+    Some(Contact {toi: entry, normal: normal})
+}
+
+// everything the rect passes over on the way from old to desired
+fn swept_bounds(old: Rect, desired: Rect) -> Rect {
+    let x = old.left().min(desired.left());
+    let y = old.top().min(desired.top());
+    Rect::new(x, y, old.right().max(desired.right()) - x, old.bot().max(desired.bot()) - y)
+}
+
 pub fn should_collide(f1: EntityForce, g1: CollisionGroup, f2: EntityForce, g2: CollisionGroup) -> bool {
     // static subject handled early
     if g1 == CollisionGroup::Bullet && g2 == CollisionGroup::Bullet { return false; }   // bullet-bullet
     if g1 == CollisionGroup::Bullet && f1 == f2 { return false; }                       // friendly fire / shooting urself on the way out
+    if (g1 == CollisionGroup::Item && g2 == CollisionGroup::Bullet) || (g1 == CollisionGroup::Bullet && g2 == CollisionGroup::Item) { return false; }   // shots go over items
     return true;
 }
 
-// chucks them into the vec
-pub fn simulate_entity_entity_collisions(entities: &HashMap<u32, Entity>, collisions: &mut Vec<CollisionEvent>, t: f32) {
+fn desired_rect(subject: &Entity, t: f32) -> Rect {
+    let dx = subject.velocity.x * t;
+    let dy = subject.velocity.y * t;
+    Rect {
+        x: subject.aabb.x + dx,
+        y: subject.aabb.y + dy,
+        w: subject.aabb.w,
+        h: subject.aabb.h,
+    }
+}
+
+fn collide_pair(subject_key: u32, subject: &Entity, t: f32, object_key: u32, object: &Entity, collisions: &mut Vec<CollisionEvent>) {
+    if subject_key == object_key {return};
+    if !(should_collide(subject.force, subject.collision_group, object.force, object.collision_group)) {return};
+    if object_key == subject.source {return};
+
+    // in the objects frame, so two things running at each other meet at the same time
+    // instead of each stopping short of where the other one was
+    let relative = subject.velocity.sub(object.velocity).mul_scalar(t);
+    if let Some(contact) = swept_aabb(subject.aabb, relative, object.aabb) {
+        collisions.push(CollisionEvent {
+            subject: subject_key,
+            object: CollisionObject::Entity(object_key),
+            toi: contact.toi,
+            normal: contact.normal,
+            subject_rect: subject.aabb,
+            object_rect: object.aabb,
+        });
+    }
+}
+
+// chucks them into the vec. only tests pairs that share a cell_size cell (use the terrain tile size)
+pub fn simulate_entity_entity_collisions(entities: &BTreeMap<u32, Entity>, collisions: &mut Vec<CollisionEvent>, t: f32, cell_size: f32) {
+    // whole sweeps go in so anything that could meet this frame shares a cell
+    let mut hash = SpatialHash::new(cell_size);
+    for (key, entity) in entities {
+        hash.insert(*key, swept_bounds(entity.aabb, desired_rect(entity, t)));
+    }
     for (subject_key, subject) in entities {
         if subject.collision_group == CollisionGroup::Static {continue};
-        // if player and own bullet continue
-
-        let dx = subject.velocity.x * t;
-        let dy = subject.velocity.y * t;
-        let subject_rect_old = subject.aabb;
-        let subject_rect_desired = Rect {
-            x: subject_rect_old.x + dx,
-            y: subject_rect_old.y + dy,
-            w: subject_rect_old.w,
-            h: subject_rect_old.h,
-        };
+        let swept = swept_bounds(subject.aabb, desired_rect(subject, t));
+        for object_key in hash.query(swept) {
+            collide_pair(*subject_key, subject, t, object_key, &entities[&object_key], collisions);
+        }
+    }
+}
 
+// every pair, same results as above. kept to check against and benchmark
+pub fn simulate_entity_entity_collisions_brute_force(entities: &BTreeMap<u32, Entity>, collisions: &mut Vec<CollisionEvent>, t: f32) {
+    for (subject_key, subject) in entities {
+        if subject.collision_group == CollisionGroup::Static {continue};
         for (object_key, object) in entities {
-            if *subject_key == *object_key {continue};
-            if !(should_collide(subject.force, subject.collision_group, object.force, object.collision_group)) {continue};
-            if *object_key == subject.source {continue};
-
-            let object_rect = object.aabb;
-
-            if rect_intersection(subject_rect_desired, object_rect) {
-                let collision_dir = rect_collision_direction(subject_rect_old, subject_rect_desired, object_rect);
-                collisions.push(CollisionEvent {
-                    dir: collision_dir,
-                    subject: *subject_key,
-                    object: CollisionObject::Entity(*object_key),
-                    subject_rect: subject.aabb,
-                    object_rect: object.aabb,
-                });
-            }
+            collide_pair(*subject_key, subject, t, *object_key, object, collisions);
         }
     }
 }
 
-pub fn simulate_entity_terrain_collisions(entities: &HashMap<u32, Entity>, terrain: &Grid, collisions: &mut Vec<CollisionEvent>, dt: f32) {
+pub fn simulate_entity_terrain_collisions(entities: &BTreeMap<u32, Entity>, terrain: &Grid, collisions: &mut Vec<CollisionEvent>, dt: f32) {
     for (subject_key, subject) in entities {
         if subject.collision_group == CollisionGroup::Static {continue};
 
-        let dx = subject.velocity.x * dt;
-        let dy = subject.velocity.y * dt;
-        let subject_rect_old = subject.aabb;
-        let subject_rect_desired = Rect {
-            x: subject_rect_old.x + dx,
-            y: subject_rect_old.y + dy,
-            w: subject_rect_old.w,
-            h: subject_rect_old.h,
-        };
-        
-        let (sx, sy) = terrain.get_xy_of_position(subject.aabb.center());
-        //println!("subject pos {} {}", sx, sy);
-        for i in -1..1+1 {
-            for j in -1..1+1 {
-                let x = sx + i;
-                let y = sy + j;
-                let t = terrain.get_2d(x, y);
-
-                if t == Some(Tile::Ground) {continue;}
-                if t == None {continue;}
-                
+        let delta = subject.velocity.mul_scalar(dt);
+
+        // every tile under the sweep so fast things cant skip a wall in one frame
+        let swept = swept_bounds(subject.aabb, desired_rect(subject, dt));
+        let (x0, y0) = terrain.get_xy_of_position(Vec2::new(swept.left(), swept.top()));
+        let (x1, y1) = terrain.get_xy_of_position(Vec2::new(swept.right(), swept.bot()));
+        for x in x0-1..=x1+1 {
+            for y in y0-1..=y1+1 {
+                if terrain.get_2d(x, y) != Some(Tile::Wall) {continue;}
+
                 let object_rect = terrain.get_rect_2d(x, y);
-                if rect_intersection(subject_rect_desired, object_rect) {
-                    let collision_dir = rect_collision_direction(subject_rect_old, subject_rect_desired, object_rect);
-                    if collision_dir == CollisionDirection::Bad {
-                        println!("bad collision!");
-                        continue;
-                    }
+                if let Some(contact) = swept_aabb(subject.aabb, delta, object_rect) {
                     collisions.push(CollisionEvent {
-                        dir: collision_dir,
                         subject: *subject_key,
                         object: CollisionObject::Terrain(x, y),
+                        toi: contact.toi,
+                        normal: contact.normal,
                         subject_rect: subject.aabb,
                         object_rect: object_rect,
                     });
@@ -119,28 +183,58 @@ pub fn simulate_entity_terrain_collisions(entities: &HashMap<u32, Entity>, terra
 }
 
 
-fn movement_bounds(subject_key: u32, collisions: &Vec<CollisionEvent>) -> (f32, f32, f32, f32) {
-    let max_dx = collisions.iter().filter(|col| col.subject == subject_key)
-        .filter(|col| col.dir == CollisionDirection::Left)
-        .map(|col| col.object_rect.left() - col.subject_rect.right())
-        .fold(f32::INFINITY, |a, b| a.min(b));
+// how far it can go along each axis this frame before it hits another entity. walls are done in slide_along_walls, items dont block
+fn movement_bounds(entities: &BTreeMap<u32, Entity>, subject_key: u32, delta: Vec2, collisions: &Vec<CollisionEvent>) -> (f32, f32, f32, f32) {
+    let (mut min_dx, mut max_dx, mut min_dy, mut max_dy) = (-f32::INFINITY, f32::INFINITY, -f32::INFINITY, f32::INFINITY);
+    for col in collisions.iter().filter(|col| col.subject == subject_key) {
+        match col.object {
+            CollisionObject::Terrain(_, _) => continue,
+            CollisionObject::Entity(id) => if entities[&id].collision_group == CollisionGroup::Item {continue},
+        }
+        if col.normal.x < 0.0 {
+            max_dx = max_dx.min(delta.x * col.toi);
+        } else if col.normal.x > 0.0 {
+            min_dx = min_dx.max(delta.x * col.toi);
+        } else if col.normal.y < 0.0 {
+            max_dy = max_dy.min(delta.y * col.toi);
+        } else if col.normal.y > 0.0 {
+            min_dy = min_dy.max(delta.y * col.toi);
+        }
+    }
+    return (min_dx, max_dx, min_dy, max_dy);
+}
 
-    let max_dy = collisions.iter().filter(|col| col.subject == subject_key)
-        .filter(|col| col.dir == CollisionDirection::Above)
-        .map(|col| col.object_rect.top() - col.subject_rect.bot())
-        .fold(f32::INFINITY, |a, b| a.min(b));
-        
-    let min_dx = collisions.iter().filter(|col| col.subject == subject_key)
-        .filter(|col| col.dir == CollisionDirection::Right)
-        .map(|col| col.object_rect.right() - col.subject_rect.left())
-        .fold(-f32::INFINITY, |a, b| a.max(b));
+// fraction of delta it can move before hitting a wall. already overlapping one only stops it going further in,
+// so rounding that leaves it a hair inside a wall cant pin it there
+fn wall_toi(terrain: &Grid, aabb: Rect, delta: Vec2) -> f32 {
+    let swept = swept_bounds(aabb, Rect::new(aabb.x + delta.x, aabb.y + delta.y, aabb.w, aabb.h));
+    let (x0, y0) = terrain.get_xy_of_position(Vec2::new(swept.left(), swept.top()));
+    let (x1, y1) = terrain.get_xy_of_position(Vec2::new(swept.right(), swept.bot()));
+    let mut toi: f32 = 1.0;
+    for x in x0-1..=x1+1 {
+        for y in y0-1..=y1+1 {
+            if terrain.get_2d(x, y) != Some(Tile::Wall) {continue;}
+            if let Some(contact) = swept_aabb(aabb, delta, terrain.get_rect_2d(x, y)) {
+                if contact.normal.x * delta.x + contact.normal.y * delta.y < 0.0 {
+                    toi = toi.min(contact.toi);
+                }
+            }
+        }
+    }
+    toi
+}
 
-    let min_dy = collisions.iter().filter(|col| col.subject == subject_key)
-        .filter(|col| col.dir == CollisionDirection::Below)
-        .map(|col| col.object_rect.bot() - col.subject_rect.top())
-        .fold(-f32::INFINITY, |a, b| a.max(b));
+// one axis at a time, bigger one first, so pushing into a wall keeps the part of the movement along it.
+// doing both at once stops dead on the seams between wall tiles and on corners
+pub fn slide_along_walls(terrain: &Grid, aabb: Rect, delta: Vec2) -> Vec2 {
+    let x_first = delta.x.abs() >= delta.y.abs();
+    let first = if x_first { Vec2::new(delta.x, 0.0) } else { Vec2::new(0.0, delta.y) };
+    let second = if x_first { Vec2::new(0.0, delta.y) } else { Vec2::new(delta.x, 0.0) };
 
-    return (min_dx, max_dx, min_dy, max_dy);
+    let first = first.mul_scalar(wall_toi(terrain, aabb, first));
+    let moved = Rect::new(aabb.x + first.x, aabb.y + first.y, aabb.w, aabb.h);
+    let second = second.mul_scalar(wall_toi(terrain, moved, second));
+    first.add(second)
 }
 
 fn clamp(val: f32, min: f32, max: f32) -> f32 {
@@ -151,61 +245,295 @@ fn clamp(val: f32, min: f32, max: f32) -> f32 {
     }
 }
 
-pub fn compute_movement(entities: &HashMap<u32, Entity>, collisions: &Vec<CollisionEvent>, movements: &mut Vec<(u32, f32, f32)>, dt: f32) {
+// clamping against the events assumes everything else gets to do its whole move. when something ends up
+// blocked by a third thing, whatever was counting on it getting out of the way would run into it,
+// so go over the near pairs again with the real moves and pull both back to where they touch.
+// cutting one axis short changes the path the other axis takes past walls, so they get clipped again too
+const RELAX_PASSES: usize = 4;
+
+fn relax_movements(entities: &BTreeMap<u32, Entity>, terrain: &Grid, moves: &mut BTreeMap<u32, Vec2>) {
+    let mut hash = SpatialHash::new(terrain.elem_w);
+    for (key, entity) in entities {
+        let m = moves[key];
+        hash.insert(*key, swept_bounds(entity.aabb, Rect::new(entity.aabb.x + m.x, entity.aabb.y + m.y, entity.aabb.w, entity.aabb.h)));
+    }
+    let blocks = |e: &Entity| e.collision_group != CollisionGroup::Bullet && e.collision_group != CollisionGroup::Item;
+    let clip = |e: &Entity, m: Vec2| if e.collision_group == CollisionGroup::Static { m } else { slide_along_walls(terrain, e.aabb, m) };
+
+    for _ in 0..RELAX_PASSES {
+        let mut changed = false;
+        for (a_key, a) in entities.iter().filter(|(_, e)| blocks(e)) {
+            let a_swept = swept_bounds(a.aabb, Rect::new(a.aabb.x + moves[a_key].x, a.aabb.y + moves[a_key].y, a.aabb.w, a.aabb.h));
+            for b_key in hash.query(a_swept).into_iter().filter(|k| k > a_key) {
+                let b = &entities[&b_key];
+                if !blocks(b) || !should_collide(a.force, a.collision_group, b.force, b.collision_group) {continue};
+                if a.source == b_key || b.source == *a_key {continue};
+
+                let (ma, mb) = (moves[a_key], moves[&b_key]);
+                let relative = ma.sub(mb);
+                let contact = match swept_aabb(a.aabb, relative, b.aabb) {
+                    Some(c) if c.toi < 1.0 && c.normal.x * relative.x + c.normal.y * relative.y < 0.0 => c,
+                    _ => continue,
+                };
+                let scale = |m: Vec2| if contact.normal.x != 0.0 { Vec2::new(m.x * contact.toi, m.y) } else { Vec2::new(m.x, m.y * contact.toi) };
+                moves.insert(*a_key, clip(a, scale(ma)));
+                moves.insert(b_key, clip(b, scale(mb)));
+                changed = true;
+            }
+        }
+        if !changed {break};
+    }
+}
+
+pub fn compute_movement(entities: &BTreeMap<u32, Entity>, terrain: &Grid, collisions: &Vec<CollisionEvent>, movements: &mut Vec<(u32, f32, f32)>, dt: f32) {
+    let mut moves = BTreeMap::new();
     for (entity_key, entity) in entities.iter() {
-        let (min_x, max_x, min_y, max_y) = movement_bounds(*entity_key, collisions);
-        let x_movt = clamp(entity.velocity.x * dt, min_x, max_x);
-        let y_movt = clamp(entity.velocity.y * dt, min_y, max_y);
+        let delta = entity.velocity.mul_scalar(dt);
+        let (min_x, max_x, min_y, max_y) = movement_bounds(entities, *entity_key, delta, collisions);
+        let clamped = Vec2::new(clamp(delta.x, min_x, max_x), clamp(delta.y, min_y, max_y));
+        let movt = if entity.collision_group == CollisionGroup::Static { clamped } else { slide_along_walls(terrain, entity.aabb, clamped) };
+        moves.insert(*entity_key, movt);
+    }
+
+    relax_movements(entities, terrain, &mut moves);
 
-        if x_movt != 0.0 || y_movt != 0.0 {
-            movements.push((*entity_key, x_movt, y_movt));
+    for (entity_key, movt) in moves {
+        if movt.x != 0.0 || movt.y != 0.0 {
+            movements.push((entity_key, movt.x, movt.y));
         }
     }
 }
 
 #[test]
-fn test_rcd() {
-    {
-        let sold = Rect::new(0.0, 0.0, 1.0, 1.0);
-        let snew = Rect::new(0.2, 0.0, 1.0, 1.0);
-        let obj = Rect::new(1.1, 0.0, 1.0, 1.0);
+fn test_swept_aabb() {
+    let obj = Rect::new(1.0, 1.0, 1.0, 1.0);
+
+    // head on from each side
+    let hit = swept_aabb(Rect::new(-0.5, 1.0, 1.0, 1.0), Vec2::new(1.0, 0.0), obj).unwrap();
+    assert_eq!(hit, Contact {toi: 0.5, normal: Vec2::new(-1.0, 0.0)});
+    let hit = swept_aabb(Rect::new(2.5, 1.0, 1.0, 1.0), Vec2::new(-1.0, 0.0), obj).unwrap();
+    assert_eq!(hit, Contact {toi: 0.5, normal: Vec2::new(1.0, 0.0)});
+    let hit = swept_aabb(Rect::new(1.0, -0.5, 1.0, 1.0), Vec2::new(0.0, 1.0), obj).unwrap();
+    assert_eq!(hit, Contact {toi: 0.5, normal: Vec2::new(0.0, -1.0)});
+    let hit = swept_aabb(Rect::new(1.0, 2.5, 1.0, 1.0), Vec2::new(0.0, -1.0), obj).unwrap();
+    assert_eq!(hit, Contact {toi: 0.5, normal: Vec2::new(0.0, 1.0)});
+
+    // too short, wrong way, sliding along the top
+    assert!(swept_aabb(Rect::new(-0.5, 1.0, 1.0, 1.0), Vec2::new(0.4, 0.0), obj).is_none());
+    assert!(swept_aabb(Rect::new(-0.5, 1.0, 1.0, 1.0), Vec2::new(-1.0, 0.0), obj).is_none());
+    assert!(swept_aabb(Rect::new(0.0, 0.0, 1.0, 1.0), Vec2::new(2.0, 0.0), obj).is_none());
+
+    // diagonally, the side it crosses last is the one it hits
+    let hit = swept_aabb(Rect::new(-0.5, 0.5, 1.0, 1.0), Vec2::new(1.0, 1.0), obj).unwrap();
+    assert_eq!(hit.normal, Vec2::new(-1.0, 0.0));
+
+    // small and fast, starts and ends clear of it
+    let bullet = Rect::new(0.0, 1.5, 0.02, 0.02);
+    assert!(!overlapping(Rect::new(3.0, 1.5, 0.02, 0.02), obj));
+    let hit = swept_aabb(bullet, Vec2::new(3.0, 0.0), obj).unwrap();
+    assert!((hit.toi - 0.98 / 3.0).abs() < 0.0001);
+
+    // already inside, out the nearest side
+    let hit = swept_aabb(Rect::new(1.8, 1.4, 0.5, 0.2), Vec2::zero(), obj).unwrap();
+    assert_eq!(hit, Contact {toi: 0.0, normal: Vec2::new(1.0, 0.0)});
+}
+
+#[test]
+fn test_fast_bullet_hits_wall() {
+    use crate::level::*;
+
+    // a long frame would put it past the wall
+    let state = parse_level("#######\n#p.#..#\n#######\n", 1).unwrap();
+    let mut entities = BTreeMap::new();
+    entities.insert(1, Entity::new_bullet(Vec2::new(0.35, 0.3), Vec2::new(1.0, 0.0), EntityForce::Player, 0));
+    let mut collisions = Vec::new();
+    simulate_entity_terrain_collisions(&entities, &state.terrain, &mut collisions, 1.0);
+    assert_eq!(collisions.len(), 1);
+    assert!(matches!(collisions[0].object, CollisionObject::Terrain(3, 1)));
+    assert_eq!(collisions[0].normal, Vec2::new(-1.0, 0.0));
+}
+
+#[test]
+fn test_broadphase_matches_brute_force() {
+    use rand::Rng;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
 
-        assert_eq!(rect_collision_direction(sold, snew, obj), CollisionDirection::Left);
+    let mut rng = ChaCha8Rng::seed_from_u64(4);
+    let mut entities = BTreeMap::new();
+    for i in 0..500 {
+        let pos = Vec2::new(rng.gen_range(0.0..2.0), rng.gen_range(0.0..2.0));
+        let entity = match i % 5 {
+            0 => Entity::new_swarmer(pos.x, pos.y),
+            1 => Entity::new_crate(pos.x, pos.y),
+            _ => Entity::new_bullet(pos, Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)).normalize(), EntityForce::Player, 0),
+        };
+        entities.insert(rng.gen(), entity);
     }
-    {
-        let sold = Rect::new(0.0, 0.0, 1.0, 1.0);
-        let snew = Rect::new(0.0, 0.2, 1.0, 1.0);
-        let obj = Rect::new(0.0, 1.1, 1.0, 1.0);
 
-        assert_eq!(rect_collision_direction(sold, snew, obj), CollisionDirection::Above);
+    let mut hashed = Vec::new();
+    let mut brute = Vec::new();
+    simulate_entity_entity_collisions(&entities, &mut hashed, 0.1, 0.2);
+    simulate_entity_entity_collisions_brute_force(&entities, &mut brute, 0.1);
+    assert!(!hashed.is_empty());
+    assert_eq!(format!("{:?}", hashed), format!("{:?}", brute));
+}
+
+#[test]
+fn test_slide_along_walls() {
+    use crate::level::*;
+
+    let state = parse_level("\
+##########
+#p.......#
+#........#
+#........#
+#........#
+##########
+", 1).unwrap();
+    let g = &state.terrain;
+    let size = 0.1;
+    let touches = |a: Rect| crate::generators::placement::touches_wall(g, a);
+
+    // down and right along the floor, every frame keeps all its x
+    let mut aabb = Rect::new(0.25, 1.0 - size, size, size);
+    for _ in 0..150 {
+        let movt = slide_along_walls(g, aabb, Vec2::new(0.01, 0.01));
+        assert!((movt.x - 0.01).abs() < 0.0001 || aabb.right() + movt.x >= 1.8 - 0.0001);
+        assert!(movt.y.abs() < 0.0001);
+        aabb.x += movt.x;
+        aabb.y += movt.y;
+        assert!(!touches(aabb));
     }
-    {
-        let sold = Rect::new(1.1, 0.0, 1.0, 1.0);
-        let snew = Rect::new(0.9, 0.0, 1.0, 1.0);
-        let obj = Rect::new(0.0, 0.0, 1.0, 1.0);
+    // and ends up tucked in the corner, where it doesnt go anywhere
+    assert!((aabb.right() - 1.8).abs() < 0.0001);
+    let movt = slide_along_walls(g, aabb, Vec2::new(0.01, 0.01));
+    assert!(movt.magnitude() < 0.0001);
+    // but can still leave
+    let movt = slide_along_walls(g, aabb, Vec2::new(-0.01, 0.01));
+    assert!((movt.x + 0.01).abs() < 0.0001);
 
-        assert_eq!(rect_collision_direction(sold, snew, obj), CollisionDirection::Right);
+    // up and left along the left wall, faster than the tiles so it has to cross seams in one go
+    let mut aabb = Rect::new(0.2, 0.9, size, size);
+    for _ in 0..20 {
+        let movt = slide_along_walls(g, aabb, Vec2::new(-0.05, -0.06));
+        aabb.x += movt.x;
+        aabb.y += movt.y;
+        assert!(!touches(aabb));
+        assert!((aabb.left() - 0.2).abs() < 0.0001);
     }
-    {
-        let sold = Rect::new(0.0, 1.1, 1.0, 1.0);
-        let snew = Rect::new(0.9, 0.9, 1.0, 1.0);
-        let obj = Rect::new(0.0, 0.0, 1.0, 1.0);
+    assert!((aabb.top() - 0.2).abs() < 0.0001);
+}
+
+#[test]
+fn test_slide_around_corner() {
+    use crate::level::*;
 
-        assert_eq!(rect_collision_direction(sold, snew, obj), CollisionDirection::Below);
+    // coming along under the overhang and out past its end
+    let state = parse_level("\
+#######
+####..#
+#p....#
+#.....#
+#######
+", 1).unwrap();
+    let g = &state.terrain;
+    let mut aabb = Rect::new(0.3, 0.4, 0.1, 0.1);
+    for _ in 0..40 {
+        let movt = slide_along_walls(g, aabb, Vec2::new(0.02, -0.02));
+        aabb.x += movt.x;
+        aabb.y += movt.y;
+        assert!(!crate::generators::placement::touches_wall(g, aabb));
     }
+    // got round the corner and up into the alcove
+    assert!((aabb.right() - 1.2).abs() < 0.0001);
+    assert!((aabb.top() - 0.2).abs() < 0.0001);
 }
 
-pub fn rect_collision_direction(subject_old: Rect, subject_desired: Rect, object: Rect) -> CollisionDirection {
-    if subject_old.bot() <= object.top() && subject_desired.bot() >= object.top() {
-    CollisionDirection::Above
-    } else if subject_old.right() <= object.left() && subject_desired.right() >= object.left() {
-        CollisionDirection::Left
-    } else if subject_old.left() >= object.right() && subject_desired.left() <= object.right() {
-        CollisionDirection::Right
-    } else if subject_old.top() >= object.bot() && subject_desired.top() <= object.bot() {
-        CollisionDirection::Below
-    } else {
-        println!("bad collision");
-        CollisionDirection::Bad
+#[test]
+fn test_head_on() {
+    use crate::level::*;
+
+    let state = parse_level("##########\n#p.......#\n#........#\n##########\n", 1).unwrap();
+    let mut entities = BTreeMap::new();
+    let mut a = Entity::new_swarmer(0.5, 0.4);
+    a.velocity = Vec2::new(0.5, 0.0);
+    let mut b = Entity::new_swarmer(1.3, 0.4);
+    b.velocity = Vec2::new(-0.3, 0.0);
+    entities.insert(1, a);
+    entities.insert(2, b);
+
+    // close the gap in a few big steps, meet where their speeds say they should and stay there
+    for _ in 0..20 { step_movement(&mut entities, &state.terrain, 0.1); }
+    let (a, b) = (entities[&1].aabb, entities[&2].aabb);
+    assert!(!overlapping(a, b));
+    assert!((b.left() - a.right()).abs() < 0.0001);
+    assert!((a.center().x - (0.5 + 0.5 * 0.75 / 0.8)).abs() < 0.0001);
+
+    // one backs off and the other can follow
+    entities.get_mut(&2).unwrap().velocity = Vec2::new(0.3, 0.0);
+    entities.get_mut(&1).unwrap().velocity = Vec2::new(0.3, 0.0);
+    step_movement(&mut entities, &state.terrain, 0.1);
+    assert!((entities[&1].aabb.x - (a.x + 0.03)).abs() < 0.0001);
+    assert!((entities[&2].aabb.x - (b.x + 0.03)).abs() < 0.0001);
+}
+
+#[test]
+fn test_crowd_doesnt_overlap() {
+    use crate::level::*;
+    use rand::Rng;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    // a ring of swarmers all running at the middle
+    let state = parse_level("##########\n#p.......#\n#........#\n#........#\n#........#\n##########\n", 1).unwrap();
+    let mut rng = ChaCha8Rng::seed_from_u64(2);
+    let mut entities = BTreeMap::new();
+    let middle = Vec2::new(1.0, 0.6);
+    for i in 0..12 {
+        let pos = middle.add(Vec2::new(0.35, 0.0).rotate(i as f32 * std::f32::consts::PI / 6.0));
+        let mut swarmer = Entity::new_swarmer(pos.x, pos.y);
+        swarmer.velocity = middle.sub(pos).normalize().mul_scalar(rng.gen_range(0.2..0.6));
+        entities.insert(rng.gen(), swarmer);
     }
-}
\ No newline at end of file
+    for _ in 0..60 { step_movement(&mut entities, &state.terrain, 1.0 / 60.0); }
+    let rects: Vec<Rect> = entities.values().map(|e| e.aabb).collect();
+    for (i, a) in rects.iter().enumerate() {
+        for b in rects.iter().skip(i + 1) {
+            let overlap = (a.right().min(b.right()) - a.left().max(b.left())).min(a.bot().min(b.bot()) - a.top().max(b.top()));
+            assert!(overlap < 0.0001);
+        }
+    }
+}
+
+#[test]
+fn test_meet_at_corner() {
+    use crate::level::*;
+    use crate::generators::placement::touches_wall;
+
+    // a comes out from under the overhang heading up into the alcove, b is in the way and pinned
+    // against the far wall. a only gets a little of its move across, not enough to clear the overhang
+    let state = parse_level("\
+#######
+####..#
+#p....#
+#.....#
+#######
+", 1).unwrap();
+    let mut entities = BTreeMap::new();
+    let mut a = Entity::new_swarmer(0.0, 0.0);
+    a.aabb = Rect::new(0.77, 0.4, 0.1, 0.1);
+    a.velocity = Vec2::new(0.4, -0.4);
+    let mut b = Entity::new_swarmer(0.0, 0.0);
+    b.aabb = Rect::new(0.88, 0.4, 0.31, 0.1);
+    b.velocity = Vec2::new(0.4, 0.0);
+    entities.insert(1, a);
+    entities.insert(2, b);
+
+    step_movement(&mut entities, &state.terrain, 0.1);
+    let (a, b) = (entities[&1].aabb, entities[&2].aabb);
+    assert!(!touches_wall(&state.terrain, a));
+    assert!(!touches_wall(&state.terrain, b));
+    assert!(!overlapping(a, b));
+    assert!(a.x > 0.77);
+}
@@ -2,20 +2,23 @@ use crate::vec2::*;
 use crate::simulation_state::*;
 use crate::systems::projectiles::*;
 use crate::entity::*;
-use rand::Rng;
+use crate::side_effect::*;
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Command {
     Walk(u32, Vec2), // walker, direction (or stop by doing 0.0)
     Look(u32, Vec2), // looker, dir
     Shoot(u32), // shooter
+    Eat(u32), // eater, has the gun theyre holding
+    Throw(u32), // thrower, the gun theyre holding goes where theyre looking
 }
 
 pub fn apply_command(state: &mut SimulationState, command: Command) {
     match command {
         Command::Walk(walker_id, direction) => {
             if let Some(mut walker) = state.entities.get_mut(&walker_id) {
-                walker.velocity = direction.mul_scalar(walker.speed);
+                let speed = if walker.is_boosted(state.time as f32) { walker.speed * SPEED_BOOST } else { walker.speed };
+                walker.velocity = direction.mul_scalar(speed);
             }
         },
         Command::Look(id, dir) => {
@@ -25,9 +28,30 @@ pub fn apply_command(state: &mut SimulationState, command: Command) {
         }
         Command::Shoot(shooter_id) => {
             if let Some(mut shooter) = state.entities.get_mut(&shooter_id) {
-                let bullets = shoot_gun(&mut shooter, shooter_id, state.time as f32);
+                let bullets = shoot_gun(&mut shooter, shooter_id, state.time as f32, &mut state.rng);
                 for bullet in bullets {
-                    state.entities.insert(rand::thread_rng().gen(), bullet);
+                    state.entities.insert(new_entity_id(&state.entities, &mut state.rng), bullet);
+                }
+                state.discard_empty_gun(shooter_id);
+            }
+        },
+        Command::Throw(id) => {
+            if let Some(gun) = state.take_gun(id) {
+                let thrower = state.entities[&id];
+                let thrown = Entity::new_thrown_gun(thrower.aabb.center(), thrower.look_direction, thrower.force, id, gun);
+                state.entities.insert(new_entity_id(&state.entities, &mut state.rng), thrown);
+            }
+        },
+        Command::Eat(id) => {
+            if let Some(gun) = state.take_gun(id) {
+                state.resolve_side_effect(SideEffect::Heal(gun.eat_heal(), PLAYER_MAX_HEALTH, id));
+                let until = state.time as f32 + gun.eat_effect.duration();
+                if let Some(eater) = state.entities.get_mut(&id) {
+                    match gun.eat_effect {
+                        EatEffect::SpeedBoost(_) => eater.boost_until = until,
+                        EatEffect::Shield(_) => eater.shield_until = until,
+                        EatEffect::None => {},
+                    }
                 }
             }
         },
@@ -1,5 +1,9 @@
 pub mod collision;
+pub mod broadphase;
 pub mod projectiles;
 pub mod command;
 pub mod ai;
-pub mod melee_damage;
\ No newline at end of file
+pub mod melee_damage;
+pub mod pickups;
+#[cfg(test)]
+pub mod testing;
@@ -0,0 +1,81 @@
+use crate::entity::*;
+use crate::simulation_state::*;
+use crate::systems::collision::*;
+use crate::side_effect::*;
+#[cfg(test)]
+use crate::systems::testing::*;
+
+// the player walking onto an item picks it up: keys open doors, guns go on the back of their queue
+pub fn handle_pickups(state: &SimulationState, collisions: &[CollisionEvent], effects: &mut Vec<SideEffect>) {
+    for col in collisions.iter() {
+        let item_id = match col.object {
+            CollisionObject::Entity(id) => id,
+            _ => continue,
+        };
+        if let (Some(subject), Some(item)) = (state.entities.get(&col.subject), state.entities.get(&item_id)) {
+            if subject.variety == EntityType::Player && item.collision_group == CollisionGroup::Item {
+                effects.push(SideEffect::PickUp(col.subject, item_id));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn step(state: &mut SimulationState, dt: f32) {
+    let collisions = step_movement(&mut state.entities, &state.terrain, dt);
+    let mut effects = Vec::new();
+    handle_pickups(state, &collisions, &mut effects);
+    for effect in effects {
+        state.resolve_side_effect(effect);
+    }
+    state.retain_entities(|_, e| e.health > 0.0);
+}
+
+#[test]
+fn test_key_opens_door() {
+    use crate::level::*;
+    use crate::vec2::*;
+
+    let mut state = parse_level("\
+#######
+#p.k..#
+####d##
+####.##
+#######
+", 1).unwrap();
+    let (player_id, _) = state.entities.iter().find(|(_, e)| e.variety == EntityType::Player).map(|(id, e)| (*id, *e)).unwrap();
+    let door_id = *state.entities.iter().find(|(_, e)| e.variety == EntityType::Door).unwrap().0;
+    state.entities.get_mut(&player_id).unwrap().velocity = Vec2::new(0.6, 0.0);
+
+    for _ in 0..60 {
+        step(&mut state, 1.0 / 60.0);
+
+        // the key doesnt get in the way, and until its picked up the door stays
+        if state.entities.values().any(|e| e.variety == EntityType::Key) {
+            assert!(state.entities.contains_key(&door_id));
+        }
+    }
+    assert!(!state.entities.values().any(|e| e.variety == EntityType::Key));
+    assert!(!state.entities.contains_key(&door_id));
+    // walked straight over it
+    assert!(state.entities[&player_id].aabb.center().x > 0.8);
+}
+
+#[test]
+fn test_gun_pickup() {
+    use crate::level::*;
+    use crate::vec2::*;
+    use crate::systems::projectiles::*;
+
+    let mut state = parse_level("#######\n#p.4..#\n#######\n", 1).unwrap();
+    let player_id = *state.entities.iter().find(|(_, e)| e.variety == EntityType::Player).unwrap().0;
+    state.entities.get_mut(&player_id).unwrap().velocity = Vec2::new(0.6, 0.0);
+    for _ in 0..60 {
+        step(&mut state, 1.0 / 60.0);
+    }
+    // gone off the floor and onto the back of the queue, behind the pistol
+    assert!(!state.entities.values().any(|e| e.variety == EntityType::GunPickup(GunKind::Shotgun)));
+    let guns = state.guns(player_id);
+    assert_eq!(guns.len(), 2);
+    assert_eq!(guns[1].num_bullets, Gun::new_shotgun().num_bullets);
+}
@@ -2,23 +2,69 @@ use crate::{side_effect::SideEffect, simulation_state::*};
 use crate::systems::collision::*;
 use crate::entity::*;
 use crate::vec2::*;
+use rand::Rng;
+
+use std::collections::BTreeMap;
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Gun {
-    damage: f32,
-    spread: f32,
-    speed: f32,
-    last_fired: f32,
-    num_bullets: i32,
+    pub damage: f32,
+    pub spread: f32,
+    pub speed: f32,
+    pub last_fired: f32,
+    pub num_bullets: i32,
     pub automatic: bool,
-    cooldown: f32,
-    ammo: i32,
+    pub cooldown: f32,
+    pub ammo: i32,
     
-    burst: i32,
-    burst_count: i32,
-    burst_cooldown: f32,
+    pub burst: i32,
+    pub burst_count: i32,
+    pub burst_cooldown: f32,
+
+    pub keep_shooting: bool,
+    pub eat_effect: EatEffect,
+}
 
-    keep_shooting: bool,
+// what else you get for eating it, on top of the health
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum EatEffect {
+    #[default]
+    None,
+    SpeedBoost(f32),    // seconds
+    Shield(f32),        // seconds
+}
+
+pub const EAT_HEAL_PER_DAMAGE: f32 = 0.02;  // for every point of damage left in it
+pub const SPEED_BOOST: f32 = 1.5;
+
+pub const THROW_SPEED: f32 = 1.2;
+pub const EXPLOSION_RADIUS: f32 = 0.25;
+pub const EXPLOSION_DAMAGE_PER_DAMAGE: f32 = 0.05;     // for every point of damage left in it, on top of 1
+
+impl EatEffect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EatEffect::None => "None",
+            EatEffect::SpeedBoost(_) => "SpeedBoost",
+            EatEffect::Shield(_) => "Shield",
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        match self {
+            EatEffect::None => 0.0,
+            EatEffect::SpeedBoost(t) | EatEffect::Shield(t) => *t,
+        }
+    }
+
+    pub fn from_name(name: &str, duration: f32) -> Option<EatEffect> {
+        match name {
+            "None" => Some(EatEffect::None),
+            "SpeedBoost" => Some(EatEffect::SpeedBoost(duration)),
+            "Shield" => Some(EatEffect::Shield(duration)),
+            _ => None,
+        }
+    }
 }
 /*
 pistol
@@ -32,6 +78,19 @@ sniper rifle
 could have d2 modifiers lol
 */
 impl Gun {
+    // all the damage its got left
+    pub fn damage_left(&self) -> f32 {
+        self.ammo.max(0) as f32 * self.num_bullets as f32 * self.damage
+    }
+
+    pub fn eat_heal(&self) -> f32 {
+        self.damage_left() * EAT_HEAL_PER_DAMAGE
+    }
+
+    pub fn explosion_damage(&self) -> f32 {
+        1.0 + self.damage_left() * EXPLOSION_DAMAGE_PER_DAMAGE
+    }
+
     pub fn new_pistol() -> Gun {
         Gun { 
             damage: 1.0, 
@@ -47,6 +106,7 @@ impl Gun {
             last_fired: 0.0,
 
             keep_shooting: false,
+            eat_effect: EatEffect::None,
         }
     }
     pub fn new_npc_gun() -> Gun {
@@ -64,6 +124,7 @@ impl Gun {
             last_fired: 0.0,
 
             keep_shooting: false,
+            eat_effect: EatEffect::None,
         }
     }
     pub fn new_sprayer_gun() -> Gun {
@@ -81,6 +142,7 @@ impl Gun {
             last_fired: 0.0,
 
             keep_shooting: false,
+            eat_effect: EatEffect::None,
         }
     }
 
@@ -100,6 +162,7 @@ impl Gun {
             burst_cooldown: 0.0, 
 
             keep_shooting: false,
+            eat_effect: EatEffect::SpeedBoost(3.0),
         }
     }
 
@@ -119,6 +182,7 @@ impl Gun {
             burst_cooldown: 0.33, 
 
             keep_shooting: true,
+            eat_effect: EatEffect::None,
         }
     }
 
@@ -138,6 +202,7 @@ impl Gun {
             burst_cooldown: 1.0, 
 
             keep_shooting: false,
+            eat_effect: EatEffect::Shield(2.0),
         }
     }
 
@@ -157,6 +222,7 @@ impl Gun {
             burst_cooldown: 1.0, 
 
             keep_shooting: false,
+            eat_effect: EatEffect::SpeedBoost(2.0),
         }
     }
     pub fn new_bigdog_gun() -> Gun {
@@ -175,10 +241,93 @@ impl Gun {
             burst_cooldown: 4.0, 
 
             keep_shooting: false,
+            eat_effect: EatEffect::None,
         }
     }
 }
 
+// the ones you can pick up
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GunKind {
+    Pistol,
+    Makina,
+    BurstRifle,
+    Shotgun,
+    Scattergun,
+}
+
+pub const ALL_GUN_KINDS: [GunKind; 5] = [GunKind::Pistol, GunKind::Makina, GunKind::BurstRifle, GunKind::Shotgun, GunKind::Scattergun];
+
+impl GunKind {
+    pub fn gun(&self) -> Gun {
+        match self {
+            GunKind::Pistol => Gun::new_pistol(),
+            GunKind::Makina => Gun::new_makina(),
+            GunKind::BurstRifle => Gun::new_burst_rifle(),
+            GunKind::Shotgun => Gun::new_shotgun(),
+            GunKind::Scattergun => Gun::new_scattergun(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GunKind::Pistol => "pistol",
+            GunKind::Makina => "makina",
+            GunKind::BurstRifle => "burst_rifle",
+            GunKind::Shotgun => "shotgun",
+            GunKind::Scattergun => "scattergun",
+        }
+    }
+}
+
+/*
+inventory is a queue: you shoot whats out until its empty, then its thrown away and the next one comes out.
+pickups go on the back, or straight into your hands if whats out is already empty
+*/
+impl SimulationState {
+    pub fn pick_up_gun(&mut self, id: u32, gun: Gun) {
+        if let Some(entity) = self.entities.get_mut(&id) {
+            if entity.gun.ammo <= 0 {
+                entity.gun = gun;
+            } else {
+                self.inventories.entry(id).or_default().push_back(gun);
+            }
+        }
+    }
+
+    // swap an empty gun for the next one if theres one queued
+    pub fn discard_empty_gun(&mut self, id: u32) {
+        if let Some(entity) = self.entities.get_mut(&id) {
+            if entity.gun.ammo > 0 { return; }
+            if let Some(next) = self.inventories.get_mut(&id).and_then(|q| q.pop_front()) {
+                entity.gun = next;
+            }
+            if self.inventories.get(&id).map(|q| q.is_empty()).unwrap_or(false) {
+                self.inventories.remove(&id);
+            }
+        }
+    }
+
+    // takes the gun out of their hands for eating or throwing, the next one comes out. None if its empty
+    pub fn take_gun(&mut self, id: u32) -> Option<Gun> {
+        let entity = self.entities.get_mut(&id)?;
+        if entity.gun.ammo <= 0 { return None; }
+        let gun = entity.gun;
+        entity.gun.ammo = 0;
+        self.discard_empty_gun(id);
+        Some(gun)
+    }
+
+    // whats out then whats queued
+    pub fn guns(&self, id: u32) -> Vec<Gun> {
+        let mut guns: Vec<Gun> = self.entities.get(&id).map(|e| e.gun).into_iter().collect();
+        if let Some(queue) = self.inventories.get(&id) {
+            guns.extend(queue.iter());
+        }
+        guns
+    }
+}
+
 // ok now we need a fn to handle shooting. I guess its in command handle
 // maybe a fn that takes &mut state and makes the bullets and shit
 // but &mut state would alias the gun?
@@ -193,7 +342,7 @@ pub fn shoot_gun(state: &mut SimulationState, shooter: u32, target_pos: Vec2) {
 
 // i think I want to go crazy with enums mmmmmm y
 
-pub fn shoot_gun(entity: &mut Entity, entity_id: u32, time: f32) -> Vec<Entity> {
+pub fn shoot_gun(entity: &mut Entity, entity_id: u32, time: f32, rng: &mut impl Rng) -> Vec<Entity> {
     let mut new_entities = Vec::new();
 
     if entity.gun.ammo <= 0 { return new_entities; }
@@ -218,7 +367,7 @@ pub fn shoot_gun(entity: &mut Entity, entity_id: u32, time: f32) -> Vec<Entity>
     for _ in 0..entity.gun.num_bullets {
         let mut bullet = Entity::new_bullet(entity.aabb.center(), entity.look_direction, entity.force, entity_id);
         bullet.melee_damage = entity.gun.damage;
-        bullet.velocity = bullet.velocity.normalize().spread(entity.gun.spread).mul_scalar(entity.gun.speed);
+        bullet.velocity = bullet.velocity.normalize().spread(entity.gun.spread, rng).mul_scalar(entity.gun.speed);
         
         new_entities.push(bullet);
     }
@@ -227,8 +376,22 @@ pub fn shoot_gun(entity: &mut Entity, entity_id: u32, time: f32) -> Vec<Entity>
 }
 
 
+// a bullet only hits the first thing in its way, not everything it would have swept through this frame
+fn first_hits(collisions: &[CollisionEvent]) -> BTreeMap<u32, usize> {
+    let mut first_hit: BTreeMap<u32, usize> = BTreeMap::new();
+    for (i, col) in collisions.iter().enumerate() {
+        let earlier = first_hit.get(&col.subject).map(|j| col.toi < collisions[*j].toi).unwrap_or(true);
+        if earlier {
+            first_hit.insert(col.subject, i);
+        }
+    }
+    first_hit
+}
+
 pub fn handle_bullet_impacts(state: &SimulationState, collisions: &Vec<CollisionEvent>, effects: &mut Vec<SideEffect>) {
-    for col in collisions.iter() {
+    let first_hit = first_hits(collisions);
+    for (i, col) in collisions.iter().enumerate() {
+        if first_hit[&col.subject] != i {continue;}
         if let Some(subject) = state.entities.get(&col.subject) {
             if subject.variety == EntityType::Bullet {
                 match col.object {
@@ -254,4 +417,135 @@ pub fn handle_bullet_impacts(state: &SimulationState, collisions: &Vec<Collision
             }
         }
     }
-}
\ No newline at end of file
+}
+
+// thrown guns go off on whatever they hit first, wall or thing, harder the more ammo was left in them
+pub fn handle_thrown_gun_impacts(state: &SimulationState, collisions: &[CollisionEvent], effects: &mut Vec<SideEffect>) {
+    let first_hit = first_hits(collisions);
+    for (i, col) in collisions.iter().enumerate() {
+        if first_hit[&col.subject] != i {continue;}
+        if let Some(subject) = state.entities.get(&col.subject) {
+            if subject.variety == EntityType::ThrownGun {
+                // movement has already stopped it where it hit
+                effects.push(SideEffect::Explosion(subject.aabb.center(), EXPLOSION_RADIUS, subject.gun.explosion_damage(), subject.force));
+                effects.push(SideEffect::Damage(999.0, col.subject));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_gun_queue() {
+    use crate::level::*;
+    use crate::systems::command::*;
+
+    let mut state = parse_level("####\n#p.#\n####\n", 1).unwrap();
+    let player_id = *state.entities.keys().next().unwrap();
+    state.entities.get_mut(&player_id).unwrap().gun.ammo = 1;
+
+    state.pick_up_gun(player_id, GunKind::Shotgun.gun());
+    state.pick_up_gun(player_id, GunKind::Pistol.gun());
+    assert_eq!(state.guns(player_id).len(), 3);
+
+    // last shot throws it away and the shotgun comes out
+    state.time = 1.0;
+    apply_command(&mut state, Command::Shoot(player_id));
+    let guns = state.guns(player_id);
+    assert_eq!(guns.len(), 2);
+    assert_eq!(guns[0].num_bullets, Gun::new_shotgun().num_bullets);
+    assert_eq!(guns[1].ammo, Gun::new_pistol().ammo);
+
+    // run out of everything and youre left holding an empty one, until you pick something up
+    state.entities.get_mut(&player_id).unwrap().gun.ammo = 0;
+    state.discard_empty_gun(player_id);
+    state.entities.get_mut(&player_id).unwrap().gun.ammo = 0;
+    state.discard_empty_gun(player_id);
+    assert_eq!(state.guns(player_id).len(), 1);
+    assert!(state.inventories.is_empty());
+    state.pick_up_gun(player_id, GunKind::Makina.gun());
+    assert_eq!(state.guns(player_id).len(), 1);
+    assert_eq!(state.entities[&player_id].gun.ammo, Gun::new_makina().ammo);
+}
+
+#[test]
+fn test_eat_gun() {
+    use crate::level::*;
+    use crate::systems::command::*;
+    use crate::side_effect::*;
+
+    let mut state = parse_level("####\n#p.#\n####\n", 1).unwrap();
+    let player_id = *state.entities.keys().next().unwrap();
+    state.entities.get_mut(&player_id).unwrap().health = 3.0;
+    state.entities.get_mut(&player_id).unwrap().gun = Gun::new_pistol();
+    state.pick_up_gun(player_id, GunKind::Shotgun.gun());
+
+    // a full pistol is 24 damage
+    apply_command(&mut state, Command::Eat(player_id));
+    assert!((state.entities[&player_id].health - (3.0 + 24.0 * EAT_HEAL_PER_DAMAGE)).abs() < 0.0001);
+    assert_eq!(state.guns(player_id).len(), 1);
+    assert!(!state.entities[&player_id].is_shielded(0.0));
+
+    // shotgun heals past full so stops at full, and shields
+    apply_command(&mut state, Command::Eat(player_id));
+    let player = state.entities[&player_id];
+    assert_eq!(player.health, PLAYER_MAX_HEALTH);
+    assert!(player.is_shielded(1.9) && !player.is_shielded(2.1));
+    state.resolve_side_effect(SideEffect::Damage(2.0, player_id));
+    assert_eq!(state.entities[&player_id].health, PLAYER_MAX_HEALTH);
+
+    // nothing left to eat
+    apply_command(&mut state, Command::Eat(player_id));
+    assert_eq!(state.entities[&player_id].health, PLAYER_MAX_HEALTH);
+
+    // makina makes you faster
+    state.pick_up_gun(player_id, GunKind::Makina.gun());
+    apply_command(&mut state, Command::Eat(player_id));
+    apply_command(&mut state, Command::Walk(player_id, Vec2::new(1.0, 0.0)));
+    let player = state.entities[&player_id];
+    assert_eq!(player.velocity.x, player.speed * SPEED_BOOST);
+}
+
+#[test]
+fn test_thrown_gun() {
+    use crate::level::*;
+    use crate::systems::command::*;
+    use crate::systems::testing::*;
+
+    let mut state = parse_level("##########\n#p.......#\n##########\n", 1).unwrap();
+    let player_id = *state.entities.keys().next().unwrap();
+    let center = |x: i32| state.terrain.get_rect_2d(x, 1).center();
+    let (near, far, behind) = (center(5), center(6), center(8));
+    state.entities.insert(1, Entity::new_enemy(near.x, near.y));
+    state.entities.insert(2, Entity::new_enemy(far.x, far.y));
+    state.entities.insert(3, Entity::new_enemy(behind.x, behind.y));
+    state.entities.get_mut(&player_id).unwrap().gun = Gun::new_pistol();
+
+    apply_command(&mut state, Command::Throw(player_id));
+    assert_eq!(state.entities[&player_id].gun.ammo, 0);
+    let (thrown_id, thrown) = state.entities.iter().find(|(_, e)| e.variety == EntityType::ThrownGun).map(|(id, e)| (*id, *e)).unwrap();
+    assert_eq!(thrown.gun.ammo, Gun::new_pistol().ammo);
+
+    // fly it until it hits the first enemy
+    let mut effects = Vec::new();
+    for _ in 0..100 {
+        let collisions = step_movement(&mut state.entities, &state.terrain, 0.02);
+        handle_thrown_gun_impacts(&state, &collisions, &mut effects);
+        if !effects.is_empty() { break; }
+    }
+    for effect in effects.iter() {
+        state.resolve_side_effect(*effect);
+    }
+
+    // 24 pistol shots worth, on both enemies in range but not the one further on or the player
+    let damage = 1.0 + 24.0 * EXPLOSION_DAMAGE_PER_DAMAGE;
+    assert!(state.entities[&thrown_id].health <= 0.0);
+    assert_eq!(state.entities[&1].health, 5.0 - damage);
+    assert_eq!(state.entities[&2].health, 5.0 - damage);
+    assert_eq!(state.entities[&3].health, 5.0);
+    assert_eq!(state.entities[&player_id].health, PLAYER_MAX_HEALTH);
+
+    // nothing to throw now
+    let n = state.entities.len();
+    apply_command(&mut state, Command::Throw(player_id));
+    assert_eq!(state.entities.len(), n);
+}
@@ -0,0 +1,21 @@
+use crate::entity::*;
+use crate::grid::*;
+use crate::systems::collision::*;
+
+use std::collections::BTreeMap;
+
+// one frame of the movement part of the game loop, for tests that dont want a whole Game.
+// hands back the collisions so the system under test can have a look at them
+pub fn step_movement(entities: &mut BTreeMap<u32, Entity>, terrain: &Grid, dt: f32) -> Vec<CollisionEvent> {
+    let mut collisions = Vec::new();
+    let mut movements = Vec::new();
+    simulate_entity_entity_collisions(entities, &mut collisions, dt, terrain.elem_w);
+    simulate_entity_terrain_collisions(entities, terrain, &mut collisions, dt);
+    compute_movement(entities, terrain, &collisions, &mut movements, dt);
+    for (id, dx, dy) in movements {
+        let e = entities.get_mut(&id).unwrap();
+        e.aabb.x += dx;
+        e.aabb.y += dy;
+    }
+    collisions
+}
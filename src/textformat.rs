@@ -0,0 +1,46 @@
+use std::io;
+
+// bits shared by the text files: leaderboard, ratings, replays, levelgen settings and snapshots.
+// each starts with a "rustlandkings <kind> <version>" line
+
+pub fn bad_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub fn header(kind: &str, version: u32) -> String {
+    format!("rustlandkings {} {}\n", kind, version)
+}
+
+// returns which of the supported versions the file is
+pub fn check_header(line: Option<&str>, kind: &str, supported: &[u32]) -> io::Result<u32> {
+    let t: Vec<&str> = line.unwrap_or("").split_whitespace().collect();
+    if t.len() != 3 || t[0] != "rustlandkings" || t[1] != kind {
+        return Err(bad_data(format!("not a {} file", kind)));
+    }
+    match t[2].parse() {
+        Ok(version) if supported.contains(&version) => Ok(version),
+        _ => Err(bad_data(format!("unsupported {} version {}", kind, t[2]))),
+    }
+}
+
+// flags are written as 0 or 1, anything else is a typo not a false
+pub fn parse_flag(s: &str) -> Option<bool> {
+    match s {
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_check_header() {
+    assert_eq!(check_header(Some("rustlandkings replay 2"), "replay", &[1, 2]).unwrap(), 2);
+    assert_eq!(check_header(Some("rustlandkings replay 1 "), "replay", &[1, 2]).unwrap(), 1);
+    assert!(check_header(Some("rustlandkings replay 3"), "replay", &[1, 2]).is_err());
+    assert!(check_header(Some("rustlandkings snapshot 1"), "replay", &[1]).is_err());
+    assert!(check_header(None, "replay", &[1]).is_err());
+
+    assert_eq!(parse_flag("1"), Some(true));
+    assert_eq!(parse_flag("0"), Some(false));
+    assert_eq!(parse_flag("l"), None);
+}
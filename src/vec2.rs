@@ -23,8 +23,8 @@ impl Vec2 {
         ) 
     }
     
-    pub fn spread(&self, amount: f32) -> Vec2 {
-        let roll = rand::thread_rng().gen_range(-amount..amount);
+    pub fn spread(&self, amount: f32, rng: &mut impl Rng) -> Vec2 {
+        let roll = rng.gen_range(-amount..amount);
         return self.rotate(roll);
     }
 }
\ No newline at end of file
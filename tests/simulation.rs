@@ -0,0 +1,23 @@
+use rustlandkings::simulation_state::*;
+use rustlandkings::systems::command::*;
+use rustlandkings::entity::*;
+use rustlandkings::vec2::*;
+use rustlandkings::level::*;
+use rustlandkings::generators::params::*;
+
+#[test]
+fn test_walk_command() {
+    let mut state = generate_level_drunk(1234, &LevelGenParams::default());
+    let (player_id, _) = state.entities.iter().find(|(_, e)| e.variety == EntityType::Player).unwrap();
+    let player_id = *player_id;
+
+    apply_command(&mut state, Command::Walk(player_id, Vec2::new(0.0, 1.0)));
+    let player = state.entities.get(&player_id).unwrap();
+    assert_eq!(player.velocity, Vec2::new(0.0, player.speed));
+}
+
+#[test]
+fn test_example_level_loads() {
+    let state = load_level("levels/arena.txt", 1).unwrap();
+    assert_eq!(state.entities.values().filter(|e| e.variety == EntityType::Player).count(), 1);
+}